@@ -0,0 +1,280 @@
+//! Opening books in the Polyglot `.bin` format. A book is a list of 16 byte entries sorted by the
+//! key of the position: the key, the move, a weight and a learn value, all big-endian. The key is
+//! a Zobrist hash of its own, built from the Random64 table of the Polyglot specification, so it
+//! has nothing to do with the hashes of `chess::Board`.
+
+pub(crate) mod builder;
+pub(crate) mod pgn;
+mod random64;
+
+use std::fs;
+use std::path::Path;
+
+use chess::{
+    get_adjacent_files, get_rank, Board, ChessMove, Color, File, Piece, Rank, Square, ALL_PIECES,
+};
+use rand::Rng;
+
+const ENTRY_SIZE: usize = 16;
+/// 768 piece-square numbers, 4 for castling rights, 8 for en passant files and 1 for the side
+pub const RANDOM64_LEN: usize = 781;
+const CASTLE_OFFSET: usize = 768;
+const EN_PASSANT_OFFSET: usize = 772;
+const TURN_OFFSET: usize = 780;
+
+pub type Random64 = [u64; RANDOM64_LEN];
+
+pub use random64::POLYGLOT_RANDOM64;
+
+/// How a move is picked when the book knows several for a position
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum BookSelection {
+    /// Always the move with the highest weight
+    Best,
+    /// Randomly, in proportion to the weights, so games don't all open the same way
+    #[default]
+    Weighted,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BookEntry {
+    pub key: u64,
+    /// The move as Polyglot packs it, see `decode_move`
+    pub raw_move: u16,
+    pub weight: u16,
+    pub learn: u32,
+}
+
+impl BookEntry {
+    fn from_bytes(bytes: &[u8]) -> Self {
+        BookEntry {
+            key: u64::from_be_bytes(bytes[0..8].try_into().expect("8 bytes")),
+            raw_move: u16::from_be_bytes(bytes[8..10].try_into().expect("2 bytes")),
+            weight: u16::from_be_bytes(bytes[10..12].try_into().expect("2 bytes")),
+            learn: u32::from_be_bytes(bytes[12..16].try_into().expect("4 bytes")),
+        }
+    }
+
+    pub fn to_bytes(self) -> [u8; ENTRY_SIZE] {
+        let mut bytes = [0; ENTRY_SIZE];
+        bytes[0..8].copy_from_slice(&self.key.to_be_bytes());
+        bytes[8..10].copy_from_slice(&self.raw_move.to_be_bytes());
+        bytes[10..12].copy_from_slice(&self.weight.to_be_bytes());
+        bytes[12..16].copy_from_slice(&self.learn.to_be_bytes());
+        bytes
+    }
+}
+
+pub struct Book {
+    entries: Vec<BookEntry>,
+    selection: BookSelection,
+}
+
+impl Book {
+    /// Reads a whole book into memory
+    pub fn open(path: impl AsRef<Path>, selection: BookSelection) -> Result<Book, String> {
+        let path = path.as_ref();
+        let bytes = fs::read(path)
+            .map_err(|error| format!("Can't read book {}: {}", path.display(), error))?;
+
+        Book::from_bytes(&bytes, selection)
+    }
+
+    /// Parses the contents of a book
+    pub fn from_bytes(bytes: &[u8], selection: BookSelection) -> Result<Book, String> {
+        if !bytes.len().is_multiple_of(ENTRY_SIZE) {
+            return Err(format!(
+                "Book size {} isn't a multiple of {} bytes",
+                bytes.len(),
+                ENTRY_SIZE
+            ));
+        }
+
+        let entries: Vec<BookEntry> = bytes
+            .chunks_exact(ENTRY_SIZE)
+            .map(BookEntry::from_bytes)
+            .collect();
+
+        // Lookups are binary searches, which need the order the format promises
+        if entries.windows(2).any(|pair| pair[0].key > pair[1].key) {
+            return Err("Book entries aren't sorted by key".to_string());
+        }
+
+        Ok(Book { entries, selection })
+    }
+
+    pub fn set_selection(&mut self, selection: BookSelection) {
+        self.selection = selection;
+    }
+
+    /// All entries of the position with this key
+    pub fn entries(&self, key: u64) -> &[BookEntry] {
+        let start = self.entries.partition_point(|entry| entry.key < key);
+        let end = start + self.entries[start..].partition_point(|entry| entry.key == key);
+        &self.entries[start..end]
+    }
+
+    /// The legal book moves of the position with their weights
+    pub fn moves(&self, board: &Board) -> Vec<(ChessMove, u16)> {
+        self.entries(polyglot_key(board))
+            .iter()
+            .filter_map(|entry| Some((decode_move(board, entry.raw_move)?, entry.weight)))
+            .collect()
+    }
+
+    /// Picks a move for the position, `None` once the game left the book
+    pub fn pick(&self, board: &Board) -> Option<ChessMove> {
+        let moves = self.moves(board);
+
+        match self.selection {
+            BookSelection::Best => moves
+                .iter()
+                .max_by_key(|(_, weight)| *weight)
+                .map(|(chess_move, _)| *chess_move),
+            BookSelection::Weighted => {
+                let total: u32 = moves.iter().map(|(_, weight)| u32::from(*weight)).sum();
+                if total == 0 {
+                    return moves.first().map(|(chess_move, _)| *chess_move);
+                }
+
+                let mut ticket = rand::thread_rng().gen_range(0..total);
+                moves.iter().find_map(|(chess_move, weight)| {
+                    if ticket < u32::from(*weight) {
+                        Some(*chess_move)
+                    } else {
+                        ticket -= u32::from(*weight);
+                        None
+                    }
+                })
+            }
+        }
+    }
+}
+
+/// Polyglot key of the position
+pub fn polyglot_key(board: &Board) -> u64 {
+    let random = &POLYGLOT_RANDOM64;
+    let mut key = 0;
+
+    for piece in ALL_PIECES {
+        for color in [Color::Black, Color::White] {
+            // Black pawn, white pawn, black knight and so on
+            let kind = piece.to_index() * 2 + usize::from(color == Color::White);
+
+            for square in board.pieces(piece) & board.color_combined(color) {
+                key ^= random[64 * kind + square.to_index()];
+            }
+        }
+    }
+
+    let white = board.castle_rights(Color::White);
+    let black = board.castle_rights(Color::Black);
+    let castling = [
+        white.has_kingside(),
+        white.has_queenside(),
+        black.has_kingside(),
+        black.has_queenside(),
+    ];
+    for (offset, _) in castling.iter().enumerate().filter(|(_, right)| **right) {
+        key ^= random[CASTLE_OFFSET + offset];
+    }
+
+    // Only counts when a pawn of the side to move stands next to the pawn that can be taken
+    if let Some(en_passant) = board.en_passant() {
+        let own_pawns = board.pieces(Piece::Pawn) & board.color_combined(board.side_to_move());
+        let neighbours =
+            get_adjacent_files(en_passant.get_file()) & get_rank(en_passant.get_rank());
+
+        if (own_pawns & neighbours).popcnt() > 0 {
+            key ^= random[EN_PASSANT_OFFSET + en_passant.get_file().to_index()];
+        }
+    }
+
+    if board.side_to_move() == Color::White {
+        key ^= random[TURN_OFFSET];
+    }
+
+    key
+}
+
+/// Turns a Polyglot move into a move of the position, `None` if it isn't legal there.
+/// Polyglot writes castling as the king taking its own rook.
+pub fn decode_move(board: &Board, raw_move: u16) -> Option<ChessMove> {
+    let square = |file: u16, rank: u16| {
+        Square::make_square(
+            Rank::from_index(usize::from(rank & 7)),
+            File::from_index(usize::from(file & 7)),
+        )
+    };
+    let dest = square(raw_move, raw_move >> 3);
+    let source = square(raw_move >> 6, raw_move >> 9);
+    let promotion = match (raw_move >> 12) & 7 {
+        1 => Some(Piece::Knight),
+        2 => Some(Piece::Bishop),
+        3 => Some(Piece::Rook),
+        4 => Some(Piece::Queen),
+        _ => None,
+    };
+
+    let dest = match castling_rook(board, source, dest) {
+        Some(king_dest) => king_dest,
+        None => dest,
+    };
+    let chess_move = ChessMove::new(source, dest, promotion);
+
+    board.legal(chess_move).then_some(chess_move)
+}
+
+/// Packs a move of the position the way Polyglot books store it
+pub fn encode_move(board: &Board, chess_move: ChessMove) -> u16 {
+    let source = chess_move.get_source();
+    let mut dest = chess_move.get_dest();
+
+    // The king moves two files when castling, Polyglot has it take the rook instead
+    if board.piece_on(source) == Some(Piece::King)
+        && source.get_file() == File::E
+        && (dest.get_file() == File::G || dest.get_file() == File::C)
+    {
+        let rook_file = if dest.get_file() == File::G {
+            File::H
+        } else {
+            File::A
+        };
+        dest = Square::make_square(dest.get_rank(), rook_file);
+    }
+
+    let promotion = match chess_move.get_promotion() {
+        Some(Piece::Knight) => 1,
+        Some(Piece::Bishop) => 2,
+        Some(Piece::Rook) => 3,
+        Some(Piece::Queen) => 4,
+        _ => 0,
+    };
+
+    dest.get_file().to_index() as u16
+        | (dest.get_rank().to_index() as u16) << 3
+        | (source.get_file().to_index() as u16) << 6
+        | (source.get_rank().to_index() as u16) << 9
+        | promotion << 12
+}
+
+// Where the king goes if moving from `source` to `dest` is castling written the Polyglot way
+fn castling_rook(board: &Board, source: Square, dest: Square) -> Option<Square> {
+    let color = board.color_on(source)?;
+    let is_own_rook =
+        board.piece_on(dest) == Some(Piece::Rook) && board.color_on(dest) == Some(color);
+
+    if board.piece_on(source) != Some(Piece::King)
+        || source.get_file() != File::E
+        || !is_own_rook
+        || source.get_rank() != dest.get_rank()
+    {
+        return None;
+    }
+
+    match dest.get_file() {
+        File::H => Some(Square::make_square(source.get_rank(), File::G)),
+        File::A => Some(Square::make_square(source.get_rank(), File::C)),
+        _ => None,
+    }
+}
@@ -0,0 +1,211 @@
+//! Builds Polyglot books from PGN games. Every move played in the first plies of the games is
+//! counted for the position it was played in, and weighted by how the game went for the side that
+//! played it: 2 points for a win and 1 for a draw, the way Polyglot weighs its own books.
+
+use std::collections::HashMap;
+use std::fs;
+
+use super::pgn::{self, GameResult, PgnGame};
+use super::{encode_move, polyglot_key, BookEntry};
+use chess::Color;
+
+const DEFAULT_MAX_PLIES: usize = 24;
+
+/// Which games, and whose moves in them, make it into the book
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ResultFilter {
+    /// Moves of both sides of every game
+    #[default]
+    All,
+    /// Moves of the sides that didn't lose
+    NoLosses,
+    /// Only the moves of the winners
+    Wins,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BuildOptions {
+    /// Plies of every game that are looked at, counted from its starting position
+    pub max_plies: usize,
+    /// Moves played fewer times than this are left out
+    pub min_count: u32,
+    pub results: ResultFilter,
+}
+
+impl Default for BuildOptions {
+    fn default() -> Self {
+        BuildOptions {
+            max_plies: DEFAULT_MAX_PLIES,
+            min_count: 1,
+            results: ResultFilter::All,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+struct MoveStats {
+    count: u32,
+    points: u64,
+}
+
+pub struct BookBuilder {
+    options: BuildOptions,
+    // Keyed by the Polyglot key of the position and the packed move
+    stats: HashMap<(u64, u16), MoveStats>,
+}
+
+impl BookBuilder {
+    pub fn new(options: BuildOptions) -> Self {
+        BookBuilder {
+            options,
+            stats: HashMap::new(),
+        }
+    }
+
+    /// Counts the moves of a game. A game with a move within the ply limit that can't be played is
+    /// left out as a whole.
+    pub fn add_game(&mut self, game: &PgnGame) -> Result<(), String> {
+        for (board, chess_move) in game.positions(self.options.max_plies)? {
+            let Some(points) = self.points(game.result, board.side_to_move()) else {
+                continue;
+            };
+
+            let key = (polyglot_key(&board), encode_move(&board, chess_move));
+            let stats = self.stats.entry(key).or_default();
+            stats.count += 1;
+            stats.points += points;
+        }
+
+        Ok(())
+    }
+
+    /// What a move of `side` is worth, `None` if the filter leaves it out. Games without a result
+    /// only count without a filter, as draws.
+    fn points(&self, result: Option<GameResult>, side: Color) -> Option<u64> {
+        let points = match result {
+            Some(GameResult::Draw) | None => 1,
+            Some(GameResult::WhiteWins) if side == Color::White => 2,
+            Some(GameResult::BlackWins) if side == Color::Black => 2,
+            Some(_) => 0,
+        };
+
+        match self.options.results {
+            ResultFilter::All => Some(points),
+            _ if result.is_none() => None,
+            ResultFilter::NoLosses => (points > 0).then_some(points),
+            ResultFilter::Wins => (points == 2).then_some(points),
+        }
+    }
+
+    /// The entries of the book, sorted by key and the best move first. Moves that were played too
+    /// rarely or never scored a point are dropped, and the weights of a position are scaled down
+    /// together when they don't fit into 16 bits.
+    pub fn entries(&self) -> Vec<BookEntry> {
+        let mut kept: Vec<((u64, u16), MoveStats)> = self
+            .stats
+            .iter()
+            .filter(|(_, stats)| stats.count >= self.options.min_count && stats.points > 0)
+            .map(|(key, stats)| (*key, *stats))
+            .collect();
+        kept.sort_by_key(|((key, raw_move), stats)| {
+            (*key, std::cmp::Reverse(stats.points), *raw_move)
+        });
+
+        let mut entries = Vec::with_capacity(kept.len());
+        for position in kept.chunk_by(|a, b| a.0 .0 == b.0 .0) {
+            // Sorted, so the first move has the most points
+            let most = position[0].1.points;
+            let scale = |points: u64| {
+                if most > u64::from(u16::MAX) {
+                    (points * u64::from(u16::MAX) / most).max(1)
+                } else {
+                    points
+                }
+            };
+
+            entries.extend(position.iter().map(|((key, raw_move), stats)| BookEntry {
+                key: *key,
+                raw_move: *raw_move,
+                weight: scale(stats.points) as u16,
+                learn: 0,
+            }));
+        }
+
+        entries
+    }
+
+    /// The book as it's written to a `.bin` file
+    pub fn to_bytes(&self) -> Vec<u8> {
+        self.entries()
+            .into_iter()
+            .flat_map(BookEntry::to_bytes)
+            .collect()
+    }
+}
+
+/// `sc0 book build --out <book.bin> [--plies <n>] [--min-count <n>]
+/// [--results all|no-losses|wins] <games.pgn>...`
+pub fn build_command(args: &[String]) -> Result<(), String> {
+    let mut options = BuildOptions::default();
+    let mut out = None;
+    let mut inputs = vec![];
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let mut value = || {
+            args.next()
+                .ok_or_else(|| format!("Missing value for {}", arg))
+        };
+
+        match arg.as_str() {
+            "--out" => out = Some(value()?.clone()),
+            "--plies" => options.max_plies = parse_number(arg, value()?)?,
+            "--min-count" => options.min_count = parse_number(arg, value()?)?,
+            "--results" => {
+                options.results = match value()?.as_str() {
+                    "all" => ResultFilter::All,
+                    "no-losses" => ResultFilter::NoLosses,
+                    "wins" => ResultFilter::Wins,
+                    other => return Err(format!("Unknown result filter {}", other)),
+                }
+            }
+            _ if arg.starts_with("--") => return Err(format!("Unknown option {}", arg)),
+            _ => inputs.push(arg.clone()),
+        }
+    }
+
+    let out = out.ok_or("No output file, expected --out <book.bin>")?;
+    if inputs.is_empty() {
+        return Err("No PGN files to build the book from".to_string());
+    }
+    let mut builder = BookBuilder::new(options);
+    let mut games = 0;
+    for path in &inputs {
+        let text = fs::read_to_string(path)
+            .map_err(|error| format!("Can't read games {}: {}", path, error))?;
+
+        for game in pgn::parse_games(&text) {
+            match builder.add_game(&game) {
+                Ok(()) => games += 1,
+                Err(message) => eprintln!("Skipping a game in {}: {}", path, message),
+            }
+        }
+    }
+
+    let bytes = builder.to_bytes();
+    fs::write(&out, &bytes).map_err(|error| format!("Can't write book {}: {}", out, error))?;
+    println!(
+        "Wrote {} entries from {} games to {}",
+        bytes.len() / super::ENTRY_SIZE,
+        games,
+        out
+    );
+
+    Ok(())
+}
+
+fn parse_number<T: std::str::FromStr>(option: &str, value: &str) -> Result<T, String> {
+    value
+        .parse()
+        .map_err(|_| format!("Invalid value {} for {}", value, option))
+}
@@ -0,0 +1,181 @@
+//! Just enough of PGN to build books from: the result and starting position of every game and
+//! its main line as SAN moves. Comments, variations, move numbers and annotations are skipped.
+
+use std::str::FromStr;
+
+use chess::{Board, ChessMove};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GameResult {
+    WhiteWins,
+    BlackWins,
+    Draw,
+}
+
+impl FromStr for GameResult {
+    type Err = ();
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        match text {
+            "1-0" => Ok(GameResult::WhiteWins),
+            "0-1" => Ok(GameResult::BlackWins),
+            "1/2-1/2" => Ok(GameResult::Draw),
+            _ => Err(()),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct PgnGame {
+    /// `None` for games that were still going or whose result is unknown (`*`)
+    pub result: Option<GameResult>,
+    /// Starting position from the `FEN` tag, the standard one if there is none
+    pub fen: Option<String>,
+    /// Main line in SAN, without move numbers and annotations
+    pub moves: Vec<String>,
+}
+
+impl PgnGame {
+    /// The first `max_plies` positions of the main line with the move played in each. Fails on
+    /// the first move that doesn't parse or isn't legal, the moves after the limit aren't looked
+    /// at.
+    pub fn positions(&self, max_plies: usize) -> Result<Vec<(Board, ChessMove)>, String> {
+        let mut board = match &self.fen {
+            Some(fen) => Board::from_str(fen).map_err(|_| format!("Invalid FEN {}", fen))?,
+            None => Board::default(),
+        };
+        let mut positions = vec![];
+
+        for san in self.moves.iter().take(max_plies) {
+            let chess_move = parse_san(&board, san)?;
+            positions.push((board, chess_move));
+            board = board.make_move_new(chess_move);
+        }
+
+        Ok(positions)
+    }
+}
+
+/// Splits a PGN file into its games, each of which starts with its tags
+pub fn parse_games(text: &str) -> Vec<PgnGame> {
+    let mut games = vec![];
+    let mut game = PgnGame::default();
+    let mut in_moves = false;
+
+    for line in text.lines() {
+        let line = line.trim();
+
+        if let Some(tag) = line.strip_prefix('[') {
+            // Tags after moves belong to the next game, even if the last one had no result token
+            if in_moves {
+                games.push(std::mem::take(&mut game));
+                in_moves = false;
+            }
+            parse_tag(tag, &mut game);
+        } else if !line.is_empty() && !line.starts_with('%') {
+            in_moves = true;
+            game.moves.push(line.to_string());
+        }
+    }
+    if in_moves {
+        games.push(game);
+    }
+
+    // The movetext was collected line by line, tokens can only be told apart once it's whole
+    for game in games.iter_mut() {
+        let movetext = game.moves.join("\n");
+        game.moves.clear();
+        parse_movetext(&movetext, game);
+    }
+
+    games
+}
+
+fn parse_tag(tag: &str, game: &mut PgnGame) {
+    let Some((name, value)) = tag.trim_end_matches(']').split_once(' ') else {
+        return;
+    };
+    let value = value.trim().trim_matches('"');
+
+    match name {
+        "Result" => game.result = value.parse().ok(),
+        "FEN" => game.fen = Some(value.to_string()),
+        _ => {}
+    }
+}
+
+fn parse_movetext(movetext: &str, game: &mut PgnGame) {
+    let mut chars = movetext.chars();
+    let mut token = String::new();
+    let mut variation_depth = 0;
+
+    while let Some(c) = chars.next() {
+        match c {
+            '{' => {
+                finish_token(&mut token, variation_depth, game);
+                for c in chars.by_ref() {
+                    if c == '}' {
+                        break;
+                    }
+                }
+            }
+            ';' => {
+                finish_token(&mut token, variation_depth, game);
+                for c in chars.by_ref() {
+                    if c == '\n' {
+                        break;
+                    }
+                }
+            }
+            '(' => {
+                finish_token(&mut token, variation_depth, game);
+                variation_depth += 1;
+            }
+            ')' => {
+                finish_token(&mut token, variation_depth, game);
+                variation_depth -= 1;
+            }
+            c if c.is_whitespace() => finish_token(&mut token, variation_depth, game),
+            c => token.push(c),
+        }
+    }
+    finish_token(&mut token, variation_depth, game);
+}
+
+fn finish_token(token: &mut String, variation_depth: i32, game: &mut PgnGame) {
+    let text = std::mem::take(token);
+    if variation_depth > 0 || text.is_empty() || text.starts_with('$') {
+        return;
+    }
+
+    if let Ok(result) = text.parse() {
+        // The tag wins if both are there
+        game.result = game.result.or(Some(result));
+        return;
+    }
+    if text == "*" {
+        return;
+    }
+
+    // Move numbers like `12.` and `12...`, sometimes glued to the move
+    let digits = text.trim_start_matches(|c: char| c.is_ascii_digit());
+    let san = match digits.strip_prefix('.') {
+        Some(_) => digits.trim_start_matches('.'),
+        // Not a move number, like the zeros of `0-0`
+        None => &text,
+    };
+    if !san.is_empty() {
+        game.moves.push(san.to_string());
+    }
+}
+
+/// Parses a SAN move, with the check marks and annotations PGN adds to it. `chess` wants
+/// promotions without the `=` and castling with letters.
+pub fn parse_san(board: &Board, san: &str) -> Result<ChessMove, String> {
+    let clean = san
+        .trim_end_matches(['+', '#', '!', '?'])
+        .replace('=', "")
+        .replace('0', "O");
+
+    ChessMove::from_san(board, &clean).map_err(|_| format!("Illegal move {}", san))
+}
@@ -0,0 +1,128 @@
+//! The engine the front-ends play through. It owns the search threads and the transposition
+//! table, so both survive from one move to the next instead of being set up for every search.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+use chess::Board;
+
+use crate::search::draw::GameHistory;
+use crate::search::shared_transposition_table::SharedTransTable;
+use crate::search::thread_pool::{SearchJob, ThreadPool};
+use crate::search::time_management::{SearchLimits, TimeManager};
+use crate::search::{SearchOptions, SearchResult, DEFAULT_THREADS};
+
+pub struct Engine {
+    pool: ThreadPool,
+    tt: Arc<SharedTransTable>,
+    // Flag and clock of the search that runs or ran last
+    stop_now: Arc<AtomicBool>,
+    timer: Option<TimeManager>,
+}
+
+impl Engine {
+    pub fn new() -> Self {
+        Engine {
+            pool: ThreadPool::new(DEFAULT_THREADS),
+            tt: Arc::new(SharedTransTable::new()),
+            stop_now: Arc::new(AtomicBool::new(false)),
+            timer: None,
+        }
+    }
+
+    /// The table searches store into, for reading the PV or how full it is
+    pub fn table(&self) -> Arc<SharedTransTable> {
+        self.tt.clone()
+    }
+
+    /// Throws away all entries and allocates a table of the new size
+    pub fn resize_hash(&mut self, size_mb: usize) {
+        self.stop();
+        self.tt = Arc::new(SharedTransTable::with_size_mb(size_mb));
+    }
+
+    pub fn clear_hash(&self) {
+        self.stop();
+        self.tt.clear();
+    }
+
+    /// Forgets everything learned in the last game, the table as well as what the threads
+    /// picked up
+    pub fn new_game(&self) {
+        self.clear_hash();
+        self.pool.new_game();
+    }
+
+    /// Starts searching `board` in the background, a search that is still running is stopped
+    /// first. `on_finish` gets the result once all threads are done, no matter whether the
+    /// search ran out of time, reached its depth or was stopped. A search with `limits.ponder` set
+    /// keeps going until `ponderhit` or `stop`.
+    pub fn go(
+        &mut self,
+        board: Board,
+        history: GameHistory,
+        limits: &SearchLimits,
+        options: &SearchOptions,
+        on_finish: impl FnOnce(&SearchResult) + Send + 'static,
+    ) {
+        self.stop();
+
+        if let Some(result) = SearchResult::game_over(&board) {
+            // Nothing to search, the result is reported right away
+            self.timer = None;
+            on_finish(&result);
+            self.pool.report(result);
+            return;
+        }
+
+        // The workers are only replaced when the number of them changes
+        if self.pool.threads() != options.threads.max(1) {
+            self.pool = ThreadPool::new(options.threads);
+        }
+
+        self.stop_now = Arc::new(AtomicBool::new(false));
+        let job = SearchJob::new(
+            board,
+            limits,
+            options.clone(),
+            self.tt.clone(),
+            self.stop_now.clone(),
+            history,
+        );
+        self.timer = Some(job.timer.clone());
+        self.pool.start(job, Some(Box::new(on_finish)));
+    }
+
+    /// The opponent played the move that is being pondered on. The search goes on with the time
+    /// budget it was started with, counted from now, and keeps everything it found so far.
+    pub fn ponderhit(&self) {
+        if let Some(timer) = &self.timer {
+            timer.ponderhit();
+        }
+    }
+
+    /// Waits for the running search to finish on its own and returns its result, or the one of
+    /// the last search
+    pub fn wait(&self) -> Option<SearchResult> {
+        self.pool.wait()
+    }
+
+    /// Searches `board` and waits for the result
+    pub fn search(
+        &mut self,
+        board: Board,
+        history: GameHistory,
+        limits: &SearchLimits,
+        options: &SearchOptions,
+    ) -> SearchResult {
+        self.go(board, history, limits, options, |_| {});
+        self.wait().expect("A finished search has a result")
+    }
+
+    /// Stops the running search and waits until its result is reported. Returns the result of
+    /// the last search, if there was one.
+    pub fn stop(&self) -> Option<SearchResult> {
+        self.stop_now.store(true, Ordering::Release);
+        self.pool.wait()
+    }
+}
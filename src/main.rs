@@ -1,81 +1,201 @@
+mod book;
+mod engine;
 mod search;
 mod tests;
+mod uci;
+use chess::Board;
 use chess::{self, BoardStatus, ChessMove};
-use chess::{Board, Color};
-use search::transposition_table;
+use book::{Book, BookSelection};
+use search::draw::GameHistory;
+use search::time_management::SearchLimits;
+use engine::Engine;
+use search::SearchOptions;
+use std::env;
 use std::io;
-use std::sync::{Arc, Mutex};
+use std::process;
 
 use std::str::FromStr;
 
 fn main() {
     env_logger::init();
-    // self_play();
-    // testing();
-    player_play();
+
+    let args: Vec<String> = env::args().collect();
+
+    // GUIs start the engine without arguments, so UCI is the default mode
+    match args.get(1).map(String::as_str) {
+        None | Some("uci") => uci::uci_loop(),
+        Some("play") => player_play(open_book(&args[2..])),
+        Some("selfplay") => self_play(open_book(&args[2..])),
+        Some("book") => book_command(&args[2..]),
+        Some("testing") => testing(),
+        Some(mode) => {
+            eprintln!("Unknown mode {}, expected uci, play, selfplay or book", mode);
+            process::exit(1);
+        }
+    }
+}
+
+fn book_command(args: &[String]) {
+    let result = match args.first().map(String::as_str) {
+        Some("build") => book::builder::build_command(&args[1..]),
+        _ => Err("Expected book build".to_string()),
+    };
+
+    if let Err(message) = result {
+        eprintln!("{}", message);
+        process::exit(1);
+    }
 }
 
-#[allow(dead_code)]
-fn self_play() {
+/// Opens the book given with `--book <path>`, `--book-best` always plays its most popular move.
+/// A book that can't be opened is reported and played without.
+fn open_book(args: &[String]) -> Option<Book> {
+    let path = args
+        .iter()
+        .position(|arg| arg == "--book")
+        .and_then(|i| args.get(i + 1))?;
+    let selection = if args.iter().any(|arg| arg == "--book-best") {
+        BookSelection::Best
+    } else {
+        BookSelection::Weighted
+    };
+
+    Book::open(path, selection)
+        .map_err(|message| eprintln!("{}", message))
+        .ok()
+}
+
+fn book_move(book: &Option<Book>, board: &Board) -> Option<ChessMove> {
+    book.as_ref().and_then(|book| book.pick(board))
+}
+
+fn self_play(book: Option<Book>) {
     let mut board = Board::default();
-    let tt_white = Arc::new(Mutex::new(transposition_table::TransTable::new()));
-    let tt_black = Arc::new(Mutex::new(transposition_table::TransTable::new()));
+    let mut history = GameHistory::new(&board, 0);
+    let mut white = Engine::new();
+    let mut black = Engine::new();
 
     loop {
-        let engine_move =
-            search::iterative_deepening_search(board, 7, Some(tt_white.clone()));
+        let engine_move = match book_move(&book, &board) {
+            Some(book_move) => book_move,
+            None => {
+                white
+                    .search(
+                        board,
+                        history.clone(),
+                        &SearchLimits::depth(7),
+                        &SearchOptions::default(),
+                    )
+                    .best_move
+            }
+        };
+        history.push(&board, engine_move);
         board = board.make_move_new(engine_move);
         println!("{}", engine_move);
 
-        if board.status() == BoardStatus::Checkmate || board.status() == BoardStatus::Stalemate {
+        if game_over(&board, &history) {
             break;
         }
 
-        let engine_move =
-            search::iterative_deepening_search(board, 7, Some(tt_black.clone()));
+        let engine_move = match book_move(&book, &board) {
+            Some(book_move) => book_move,
+            None => {
+                black
+                    .search(
+                        board,
+                        history.clone(),
+                        &SearchLimits::depth(7),
+                        &SearchOptions::default(),
+                    )
+                    .best_move
+            }
+        };
+        history.push(&board, engine_move);
         board = board.make_move_new(engine_move);
         println!("{}", engine_move);
 
-        if board.status() == BoardStatus::Checkmate || board.status() == BoardStatus::Stalemate {
+        if game_over(&board, &history) {
             break;
         }
     }
 }
 
-#[allow(dead_code)]
-fn player_play() {
+fn player_play(book: Option<Book>) {
     let mut board = Board::default();
-    let tt = Arc::new(Mutex::new(transposition_table::TransTable::new()));
+    let mut history = GameHistory::new(&board, 0);
+    let mut engine = Engine::new();
+    let options = SearchOptions::default();
+    let limits = SearchLimits::depth(7);
+    let mut expected_reply = None;
 
     loop {
         let mut buffer = String::new();
         let stdin = io::stdin(); // We get `Stdin` here.
         stdin.read_line(&mut buffer).unwrap();
         let player_move = ChessMove::from_str(buffer.trim()).unwrap();
+        history.push(&board, player_move);
         board = board.make_move_new(player_move);
 
-        if board.status() == BoardStatus::Checkmate || board.status() == BoardStatus::Stalemate {
+        if game_over(&board, &history) {
             break;
         }
 
-        let engine_move =
-            search::iterative_deepening_search(board,7, Some(tt.clone()));
+        // A correct guess lets the ponder search finish the job, a wrong one is thrown away. Book
+        // moves don't need either and have no line to ponder on.
+        let (engine_move, pv) = if let Some(book_move) = book_move(&book, &board) {
+            engine.stop();
+            (book_move, vec![book_move])
+        } else if expected_reply == Some(player_move) {
+            engine.ponderhit();
+            let result = engine.wait().expect("The ponder search was started");
+            (result.best_move, result.pv)
+        } else {
+            let result = engine.search(board, history.clone(), &limits, &options);
+            (result.best_move, result.pv)
+        };
+        history.push(&board, engine_move);
         board = board.make_move_new(engine_move);
         println!("Engine move: {}", engine_move);
 
-        if board.status() == BoardStatus::Checkmate || board.status() == BoardStatus::Stalemate {
+        if game_over(&board, &history) {
             break;
         }
+
+        // Think about the expected reply while the player does, unless it ends the game
+        expected_reply = pv
+            .get(1)
+            .copied()
+            .filter(|reply| board.make_move_new(*reply).status() == BoardStatus::Ongoing);
+        if let Some(reply) = expected_reply {
+            let mut ponder_history = history.clone();
+            ponder_history.push(&board, reply);
+            let ponder_limits = SearchLimits {
+                ponder: true,
+                ..limits.clone()
+            };
+            engine.go(
+                board.make_move_new(reply),
+                ponder_history,
+                &ponder_limits,
+                &options,
+                |_| {},
+            );
+        }
     }
 }
 
-#[allow(dead_code)]
+/// Mate, stalemate or a draw by rule. Only threefold repetitions end the game, so every
+/// position in the history counts as played before the search.
+fn game_over(board: &Board, history: &GameHistory) -> bool {
+    board.status() != BoardStatus::Ongoing || history.is_draw(board, history.len())
+}
+
 fn testing() {
     /*
     let board = Board::from_str("4k2r/1R3R2/p3p1pp/4b3/1BnNr3/8/P1P5/5K2 w - - 1 0")
         .expect("Invalid FEN");
     let moves = MoveGen::new_legal(&board).collect();
-    let tt = Arc::new(Mutex::new(transposition_table::TransTable::new()));
+    let tt = Arc::new(SharedTransTable::new());
 
     let best_move = search::negamax_root(board, -f32::INFINITY, f32::INFINITY, 8, moves, tt);
 
@@ -86,7 +206,15 @@ fn testing() {
     // Tests the response of engine after e5 from vienna gambit accepted.
     let board = Board::from_str("rnbqkb1r/pppp1ppp/5n2/4P3/5p2/2N5/PPPP2PP/R1BQKBNR b KQkq - 0 4")
         .expect("Invalid FEN");
-    let best_move = search::iterative_deepening_search(board, 7, None);
+    let best_move = search::iterative_deepening_search(
+        board,
+        &SearchLimits::depth(7),
+        &SearchOptions::default(),
+        None,
+        None,
+        None,
+    )
+    .best_move;
     // Two possible variations, both are correct
     assert!(best_move.to_string() == "f6g8" || best_move.to_string() == "d8e7");
 }
@@ -2,29 +2,99 @@ use std::cmp::Ordering;
 use std::fmt;
 use std::fmt::Display;
 use std::sync::atomic::AtomicBool;
+use std::sync::LazyLock;
 use std::thread;
+use std::time::Duration;
 
-use chess::{Board, ChessMove, MoveGen};
+use chess::{Board, BoardStatus, ChessMove, MoveGen, Piece, Square};
 use log::debug;
 use rand::seq::SliceRandom;
 use rand::thread_rng;
 use std::sync::Arc;
-use std::sync::Mutex;
 
-use transposition_table::{Flag, TransTable, TransTableEntry};
+use draw::GameHistory;
+use evaluate::pawns::{self, PawnTable};
+use move_ordering::{MoveOrdering, MAX_PLY};
+use score::Score;
+use time_management::{SearchLimits, TimeManager};
+use shared_transposition_table::SharedTransTable;
+use thread_pool::{SearchJob, ThreadPool};
+use transposition_table::{Flag, TransTableEntry};
 
 use crate::search::utils::dump_top_moves;
 
-mod evaluate;
+pub(crate) mod draw;
+pub(crate) mod evaluate;
+pub(crate) mod move_ordering;
+pub(crate) mod score;
+pub(crate) mod see;
+pub(crate) mod shared_transposition_table;
+pub(crate) mod thread_pool;
+pub(crate) mod time_management;
 pub(crate) mod transposition_table;
 pub(crate) mod utils;
 
-const HELPER_THREADS: i32 = 3;
+/// Threads a search runs on if nobody asks for something else
+pub const DEFAULT_THREADS: usize = 1;
+// Depth the iterative deepening starts at
+const FIRST_DEPTH: i32 = 2;
+// How often a finished search looks whether pondering is over
+const PONDER_POLL_INTERVAL: Duration = Duration::from_millis(5);
+// Added to every vote so the thread with the worst score still counts for something
+const VOTE_SCORE_OFFSET: i32 = 15;
+/// Deepest iteration a search without a depth limit will start
+pub const MAX_DEPTH: i32 = 64;
+// How many nodes are searched between looking at the clock
+const NODES_BETWEEN_TIME_CHECKS: u64 = 1024;
+// Safety margin for delta pruning, covers positional swings a capture can cause
+const DELTA_MARGIN: Score = Score::cp(200);
+// Null move searches are this much shallower, and one more ply above `NULL_MOVE_DEEP_DEPTH`
+const NULL_MOVE_REDUCTION: i32 = 2;
+const NULL_MOVE_DEEP_DEPTH: i32 = 6;
+// From this depth on a null move cutoff is only trusted after a normal search confirms it
+const NULL_MOVE_VERIFICATION_DEPTH: i32 = 5;
+// Half width of the first aspiration window, doubled every time the score falls outside
+const ASPIRATION_WINDOW: i32 = 25;
+// Shallower depths are searched with a full window, their scores are too unstable
+const ASPIRATION_MIN_DEPTH: i32 = 4;
+// Reverse futility pruning trusts the static evaluation up to this depth, with this margin per ply
+const REVERSE_FUTILITY_DEPTH: i32 = 3;
+const REVERSE_FUTILITY_MARGIN: i32 = 90;
+// How far the static evaluation may be below alpha before razoring, indexed by depth
+const RAZOR_MARGINS: [i32; 3] = [0, 250, 400];
+// How much a quiet move may gain at most near the leaves, indexed by depth
+const FUTILITY_MARGINS: [i32; 3] = [0, 150, 300];
+// Extensions a single line may get in total, so checks back and forth can't blow up the tree
+pub(crate) const MAX_LINE_EXTENSIONS: i32 = 12;
+// Singular extensions need a hash move from a search at most this much shallower
+const SINGULAR_MIN_DEPTH: i32 = 6;
+const SINGULAR_TT_DEPTH_MARGIN: i32 = 3;
+// The other moves have to stay this far below the hash move's score per ply of depth
+const SINGULAR_MARGIN_PER_PLY: i32 = 3;
+// Late move reductions start this deep and after this many moves were searched at full depth
+const LMR_MIN_DEPTH: i32 = 3;
+const LMR_FULL_DEPTH_MOVES: usize = 3;
+// Late move pruning only searches this many quiet moves at the given depth, indexed by depth
+pub(crate) const LMP_MOVE_COUNTS: [usize; 4] = [0, 5, 8, 13];
+
+// How many plies late moves are reduced by, indexed by depth and move number. Both grow slowly,
+// so a log of each keeps the reductions reasonable.
+pub(crate) static REDUCTIONS: LazyLock<[[i32; 64]; 64]> = LazyLock::new(|| {
+    let mut reductions = [[0; 64]; 64];
+
+    for (depth, row) in reductions.iter_mut().enumerate().skip(1) {
+        for (move_number, reduction) in row.iter_mut().enumerate().skip(1) {
+            *reduction = (0.5 + (depth as f64).ln() * (move_number as f64).ln() / 2.5) as i32;
+        }
+    }
+
+    reductions
+});
 
 #[derive(Debug, Copy, Clone)]
 pub struct MoveEval {
     chess_move: ChessMove,
-    eval: f32,
+    eval: Score,
 }
 
 impl PartialOrd for MoveEval {
@@ -43,13 +113,7 @@ impl PartialEq for MoveEval {
 
 impl Ord for MoveEval {
     fn cmp(&self, other: &Self) -> Ordering {
-        if self.eval > other.eval {
-            Ordering::Greater
-        } else if self.eval < other.eval {
-            Ordering::Less
-        } else {
-            Ordering::Equal
-        }
+        self.eval.cmp(&other.eval)
     }
 }
 
@@ -59,29 +123,305 @@ impl Display for MoveEval {
     }
 }
 
-// Uses iterative deepening technique and transposition tables to optimize faster search
+/// Algorithm that searches each depth of the iterative deepening at the root
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum RootSearch {
+    /// Principal variation search inside aspiration windows around the last score
+    #[default]
+    Pvs,
+    /// Repeated null window searches that converge on the score
+    Mtdf,
+}
+
+/// How a search goes about finding the move, as opposed to how long it may take
+#[derive(Clone, Debug)]
+pub struct SearchOptions {
+    pub root_search: RootSearch,
+    /// Extend captures that take back on the square of the capture just before
+    pub recapture_extensions: bool,
+    /// Extend pawn pushes to the seventh rank
+    pub passed_pawn_extensions: bool,
+    /// Search threads including the main one, the others are Lazy SMP helpers
+    pub threads: usize,
+}
+
+impl Default for SearchOptions {
+    fn default() -> Self {
+        SearchOptions {
+            root_search: RootSearch::default(),
+            recapture_extensions: false,
+            passed_pawn_extensions: false,
+            threads: DEFAULT_THREADS,
+        }
+    }
+}
+
+/// State every search thread carries through the tree
+pub struct SearchContext {
+    options: SearchOptions,
+    tt: Arc<SharedTransTable>,
+    stop_now: Arc<AtomicBool>,
+    timer: TimeManager,
+    ordering: MoveOrdering,
+    pawn_table: PawnTable,
+    /// Positions of the game followed by the ones on the path to the current node
+    history: GameHistory,
+    // Length of the history when the search started, positions after that are part of the tree
+    search_start: usize,
+    // Set for the node right after a null move, which must not answer with another one
+    pub(crate) no_null_move: bool,
+    // Plies of extensions on the path to the current node
+    pub(crate) extensions: i32,
+    // Square the move made at each ply captured on, for recapture extensions
+    capture_squares: [Option<Square>; MAX_PLY],
+    pub(crate) nodes: u64,
+}
+
+impl SearchContext {
+    pub fn new(
+        options: SearchOptions,
+        tt: Arc<SharedTransTable>,
+        stop_now: Arc<AtomicBool>,
+        timer: TimeManager,
+        history: GameHistory,
+    ) -> Self {
+        SearchContext {
+            options,
+            tt,
+            stop_now,
+            timer,
+            ordering: MoveOrdering::new(),
+            pawn_table: PawnTable::new(),
+            search_start: history.len(),
+            history,
+            no_null_move: false,
+            extensions: 0,
+            capture_squares: [None; MAX_PLY],
+            nodes: 0,
+        }
+    }
+
+    /// Gets the context of a worker ready for the next search. The move ordering and pawn tables
+    /// are kept, everything that belongs to the position of the last search is replaced.
+    fn new_search(
+        &mut self,
+        options: SearchOptions,
+        tt: Arc<SharedTransTable>,
+        stop_now: Arc<AtomicBool>,
+        timer: TimeManager,
+        history: GameHistory,
+    ) {
+        self.options = options;
+        self.tt = tt;
+        self.stop_now = stop_now;
+        self.timer = timer;
+        self.ordering.new_search();
+        self.search_start = history.len();
+        self.history = history;
+        self.no_null_move = false;
+        self.extensions = 0;
+        self.capture_squares = [None; MAX_PLY];
+        self.nodes = 0;
+    }
+
+    /// Counts a node and tells whether the search has to be abandoned. The clock is only looked
+    /// at every few nodes, running out of time raises `stop_now` for all threads.
+    fn should_stop(&mut self) -> bool {
+        self.nodes += 1;
+
+        if self.nodes.is_multiple_of(NODES_BETWEEN_TIME_CHECKS) && self.timer.out_of_time() {
+            self.stop_now.store(true, std::sync::atomic::Ordering::Release);
+        }
+
+        self.stop_now.load(std::sync::atomic::Ordering::Relaxed)
+    }
+
+    /// Remembers whether the move about to be searched at `ply` captures, `None` is a null move
+    pub(crate) fn set_capture_square(&mut self, ply: i32, board: &Board, chess_move: Option<ChessMove>) {
+        if let Some(square) = self.capture_squares.get_mut(ply as usize) {
+            *square = chess_move
+                .filter(|chess_move| move_ordering::captured_piece(board, *chess_move).is_some())
+                .map(|chess_move| chess_move.get_dest());
+        }
+    }
+
+    /// How many plies to extend `chess_move` by, which leads from `board` to `new_board`
+    pub(crate) fn extension(
+        &self,
+        board: &Board,
+        new_board: &Board,
+        chess_move: ChessMove,
+        ply: i32,
+        singular: bool,
+    ) -> i32 {
+        if self.extensions >= MAX_LINE_EXTENSIONS {
+            return 0;
+        }
+
+        let gives_check = new_board.checkers().popcnt() > 0;
+        let recapture = self.options.recapture_extensions
+            && ply > 0
+            && self.capture_squares[ply as usize - 1] == Some(chess_move.get_dest())
+            && move_ordering::captured_piece(board, chess_move).is_some();
+        let passed_pawn_push = self.options.passed_pawn_extensions
+            && board.piece_on(chess_move.get_source()) == Some(Piece::Pawn)
+            && pawns::relative_rank(chess_move.get_dest(), board.side_to_move()) == 6;
+
+        if gives_check || singular || recapture || passed_pawn_push {
+            1
+        } else {
+            0
+        }
+    }
+}
+
+/// Outcome of a search: the move to play, what it is expected to lead to and how deep it was
+/// searched
+#[derive(Debug, Clone)]
+pub struct SearchResult {
+    pub best_move: ChessMove,
+    pub eval: Score,
+    pub depth: i32,
+    /// Expected line starting with `best_move`
+    pub pv: Vec<ChessMove>,
+}
+
+impl SearchResult {
+    /// The result for a position without legal moves, `None` if there is something to search.
+    /// There is no move to play, the score tells whether the side to move is mated or stalemated.
+    pub fn game_over(board: &Board) -> Option<SearchResult> {
+        let eval = match board.status() {
+            BoardStatus::Ongoing => return None,
+            BoardStatus::Checkmate => Score::mated_in(0),
+            BoardStatus::Stalemate => Score::DRAW,
+        };
+
+        Some(SearchResult {
+            best_move: ChessMove::default(),
+            eval,
+            depth: 0,
+            pv: vec![],
+        })
+    }
+}
+
+// Uses iterative deepening technique and transposition tables to optimize faster search.
+// The search ends at the depth or time given in `limits`, or when the external `stop_now` flag
+// is raised, with the best move of the last completed depth. All threads are joined before it
+// returns, and their results decide the move together. Without a `history` of the game, only
+// repetitions within the search itself are recognized.
 pub fn iterative_deepening_search(
     board: Board,
-    target_depth: i32,
-    tt_raw: Option<Arc<Mutex<TransTable>>>,
-) -> ChessMove {
-    // start with depth 4
-    let mut depth = 2;
-    let mut initial_guess = 0.0;
-    let mut tt: Arc<Mutex<TransTable>> =
-        Arc::new(Mutex::new(transposition_table::TransTable::new()));
-    let mut best_move: Option<MoveEval> = None;
-    let mut rng = thread_rng();
-    let stop_now = Arc::new(AtomicBool::new(false));
-    // let mut handles = vec![];
+    limits: &SearchLimits,
+    options: &SearchOptions,
+    tt_raw: Option<Arc<SharedTransTable>>,
+    stop_raw: Option<Arc<AtomicBool>>,
+    history_raw: Option<&GameHistory>,
+) -> SearchResult {
+    if let Some(result) = SearchResult::game_over(&board) {
+        return result;
+    }
+
+    let tt = tt_raw.unwrap_or_else(|| Arc::new(SharedTransTable::new()));
+    let stop_now = stop_raw.unwrap_or_else(|| Arc::new(AtomicBool::new(false)));
+    let history = history_raw
+        .cloned()
+        .unwrap_or_else(|| GameHistory::new(&board, 0));
+    let job = SearchJob::new(board, limits, options.clone(), tt, stop_now, history);
 
-    if let Some(external_table) = tt_raw {
-        tt = external_table;
+    // A single search has no use for threads that outlive it, an `Engine` keeps them instead
+    ThreadPool::new(options.threads).search(job)
+}
+
+/// The part of a search one worker of the pool runs. Lazy SMP: the helpers run their own
+/// iterative deepening on the same position and only share the transposition table. Every other
+/// helper skips the first depth of the main thread and then goes through the same depths, and they
+/// all start with the root moves shuffled, so they fill the table with different parts of the
+/// tree.
+fn thread_search(
+    index: usize,
+    job: &SearchJob,
+    context: &mut SearchContext,
+) -> Option<ThreadResult> {
+    let mut possible_moves: Vec<ChessMove> = MoveGen::new_legal(&job.board).collect();
+
+    if index == 0 {
+        let result = deepen(
+            job.board,
+            possible_moves,
+            FIRST_DEPTH.min(job.target_depth),
+            job.target_depth,
+            Some(&job.timer),
+            context,
+        );
+
+        // While pondering or in an infinite search the move must not be reported before `stop`,
+        // even with nothing left to search
+        while (job.infinite || job.timer.is_pondering())
+            && !job.stop_now.load(std::sync::atomic::Ordering::Relaxed)
+        {
+            thread::sleep(PONDER_POLL_INTERVAL);
+        }
+
+        result
+    } else {
+        possible_moves.shuffle(&mut thread_rng());
+        let first_depth = (FIRST_DEPTH + (index % 2) as i32).min(job.target_depth);
+        deepen(
+            job.board,
+            possible_moves,
+            first_depth,
+            job.target_depth,
+            None,
+            context,
+        )
     }
+}
 
-    let possible_moves: Vec<ChessMove> = MoveGen::new_legal(&board).collect();
+/// Puts together what the threads of a search found
+fn final_result(job: &SearchJob, results: &[ThreadResult]) -> SearchResult {
+    let chosen = vote(results);
+    let mut pv = vec![chosen.best_move.chess_move];
+    pv.extend(job.tt.principal_variation(
+        job.board.make_move_new(chosen.best_move.chess_move),
+        MAX_DEPTH as usize,
+    ));
+
+    SearchResult {
+        best_move: chosen.best_move.chess_move,
+        eval: chosen.best_move.eval,
+        depth: chosen.depth,
+        pv,
+    }
+}
+
+/// Best move of the deepest iteration a search thread completed
+#[derive(Debug, Clone, Copy)]
+struct ThreadResult {
+    best_move: MoveEval,
+    depth: i32,
+}
+
+/// Iterative deepening of a single thread, from `first_depth` up to `target_depth`. Only the main
+/// thread has a `timer` to decide whether another depth fits in the time, the helpers just go on
+/// until `stop_now` is raised. Returns nothing if not even the first depth was completed.
+fn deepen(
+    board: Board,
+    mut possible_moves: Vec<ChessMove>,
+    first_depth: i32,
+    target_depth: i32,
+    timer: Option<&TimeManager>,
+    context: &mut SearchContext,
+) -> Option<ThreadResult> {
+    let mut best: Option<ThreadResult> = None;
+    let mut initial_guess = Score::DRAW;
+
+    for depth in first_depth..=target_depth {
+        // A new depth takes longer than all the previous ones, don't start it if it can't finish
+        if best.is_some() && timer.is_some_and(|timer| !timer.can_start_iteration()) {
+            break;
+        }
 
-    while depth < target_depth + 1 {
         // the best moves from the last iteration are searched first to improve alpha-beta pruning performance
         debug!(
             "Evaluating {} positions with depth {}",
@@ -89,91 +429,123 @@ pub fn iterative_deepening_search(
             depth
         );
 
-        for _ in 0..HELPER_THREADS {
-            // Shuffle the possible moves for better performance
-            let mut thread_local_move_list = possible_moves.clone();
-            thread_local_move_list.shuffle(&mut rng);
-            let thread_local_tt = tt.clone();
-            let thread_local_stop_now = stop_now.clone();
-
-            thread::spawn(move || {
-                negamax(
-                    board,
-                    target_depth,
-                    target_depth,
-                    -f32::INFINITY,
-                    f32::INFINITY,
-                    thread_local_tt,
-                    thread_local_stop_now,
-                );
-            });
-        }
-
-        // Need to rethink... this may result in two copies of the transposition table at once
-        let search_result = mtdf(
-            board,
+        let search_result = match context.options.root_search {
+            RootSearch::Pvs => {
+                aspiration_search(board, depth, initial_guess, &possible_moves, context)
+            }
+            RootSearch::Mtdf => mtdf(board, depth, initial_guess, &possible_moves, context),
+        }
+        .expect("Got empty response from the root search");
+
+        // An interrupted iteration is incomplete, so keep the result of the previous depth. The
+        // main thread has nothing else to fall back on.
+        if context.stop_now.load(std::sync::atomic::Ordering::Relaxed)
+            && (best.is_some() || timer.is_none())
+        {
+            break;
+        }
+
+        best = Some(ThreadResult {
+            best_move: search_result[0],
             depth,
-            initial_guess,
-            &possible_moves,
-            tt.clone(),
-            stop_now.clone(),
-        )
-        .expect("Got empty response from MTDF");
-        best_move = Some(search_result[0]);
+        });
+        order_root_moves(&mut possible_moves, &search_result);
 
-        if search_result[0].eval > 175.0 {
-            return search_result[0].chess_move;
+        // No need to look further once a forced mate is found
+        if is_winning_mate(search_result[0].eval) {
+            break;
         }
         // Best move from last depth is the first guess for current depth.
         // keep inital guesses at 0, since using different guesses misleads the engine
-        initial_guess = best_move.unwrap().eval;
+        initial_guess = search_result[0].eval;
+    }
+
+    best
+}
 
-        depth += 1;
+/// Picks the result to play from those of all threads. Every thread votes for its move, with
+/// more weight the deeper it got and the better its score, so a helper that completed a deeper
+/// depth can overrule the main thread. Among the threads that agree on the winning move, the
+/// deepest one supplies the score and depth. A forced mate needs no votes, the shortest one found
+/// is played right away.
+fn vote(results: &[ThreadResult]) -> ThreadResult {
+    if let Some(mate) = results
+        .iter()
+        .filter(|result| is_winning_mate(result.best_move.eval))
+        .max_by_key(|result| result.best_move.eval)
+    {
+        return *mate;
     }
 
-    stop_now.store(true, std::sync::atomic::Ordering::Release);
+    let worst = results
+        .iter()
+        .map(|result| result.best_move.eval)
+        .min()
+        .expect("At least one thread result");
+    let votes = |chess_move: ChessMove| -> i64 {
+        results
+            .iter()
+            .filter(|result| result.best_move.chess_move == chess_move)
+            .map(|result| {
+                let margin = (result.best_move.eval - worst).centipawns() + VOTE_SCORE_OFFSET;
+                i64::from(margin) * i64::from(result.depth)
+            })
+            .sum()
+    };
+
+    *results
+        .iter()
+        .max_by_key(|result| (votes(result.best_move.chess_move), result.depth))
+        .expect("At least one thread result")
+}
 
-    best_move.unwrap().chess_move
+/// Sorts the root moves by the scores of the last iteration. Moves that weren't searched because
+/// of a cutoff keep their relative order behind the ones that were.
+fn order_root_moves(moves: &mut [ChessMove], last_results: &[MoveEval]) {
+    moves.sort_by_key(|chess_move| {
+        last_results
+            .iter()
+            .position(|result| result.chess_move == *chess_move)
+            .unwrap_or(last_results.len())
+    });
 }
 
 fn mtdf(
     board: Board,
     depth: i32,
-    first_guess: f32,
+    first_guess: Score,
     possible_moves: &[ChessMove],
-    tt: Arc<Mutex<TransTable>>,
-    stop_now: Arc<AtomicBool>,
+    context: &mut SearchContext,
 ) -> Option<Vec<MoveEval>> {
     // Not named alpha and beta for clarity's sake
     let mut guess = first_guess;
-    let mut upperbound = f32::INFINITY;
-    let mut lowerbound = -f32::INFINITY;
-    let mut best_moves: Option<Vec<MoveEval>> = None;
+    let mut upperbound = Score::INFINITY;
+    let mut lowerbound = -Score::INFINITY;
+    let mut best_moves: Option<Vec<MoveEval>>;
 
     debug!("First guess: {}", first_guess);
 
     loop {
         let beta = if guess == lowerbound {
-            guess + 1.0
+            guess + Score::cp(1)
         } else {
             guess
         };
 
         let results = negamax_root(
             board,
-            beta - 1.0,
+            beta - Score::cp(1),
             beta,
             depth,
             possible_moves,
-            tt.clone(),
-            stop_now.clone(),
+            context,
         );
         dump_top_moves(&results);
 
         guess = results[0].eval;
         best_moves = Some(results);
 
-        if guess > 170.0 {
+        if is_winning_mate(guess) {
             return best_moves;
         }
 
@@ -188,7 +560,8 @@ fn mtdf(
             upperbound, lowerbound, beta, depth
         );
 
-        if lowerbound >= upperbound || stop_now.load(std::sync::atomic::Ordering::Relaxed) {
+        if lowerbound >= upperbound || context.stop_now.load(std::sync::atomic::Ordering::Relaxed)
+        {
             break;
         }
     }
@@ -196,18 +569,58 @@ fn mtdf(
     best_moves
 }
 
+/// Searches the root with a window around the score of the last depth, since the new score is
+/// most likely close to it and a narrow window cuts off more. A score outside the window is only
+/// a bound, so the window is widened on that side and the depth searched again.
+fn aspiration_search(
+    board: Board,
+    depth: i32,
+    previous_score: Score,
+    possible_moves: &[ChessMove],
+    context: &mut SearchContext,
+) -> Option<Vec<MoveEval>> {
+    let mut delta = ASPIRATION_WINDOW;
+    let (mut alpha, mut beta) = if depth < ASPIRATION_MIN_DEPTH || previous_score.is_mate() {
+        (-Score::INFINITY, Score::INFINITY)
+    } else {
+        (
+            previous_score - Score::cp(delta),
+            previous_score + Score::cp(delta),
+        )
+    };
+
+    loop {
+        let results = negamax_root(board, alpha, beta, depth, possible_moves, context);
+        dump_top_moves(&results);
+
+        let score = results[0].eval;
+        if context.stop_now.load(std::sync::atomic::Ordering::Relaxed) {
+            return Some(results);
+        }
+
+        if score <= alpha {
+            alpha = (alpha - Score::cp(delta)).max(-Score::INFINITY);
+        } else if score >= beta {
+            beta = (beta + Score::cp(delta)).min(Score::INFINITY);
+        } else {
+            return Some(results);
+        }
+
+        debug!("Aspiration window failed, widening to {} {}", alpha, beta);
+        delta *= 2;
+    }
+}
+
 pub fn negamax_root(
     board: Board,
-    mut alpha: f32,
-    beta: f32,
+    mut alpha: Score,
+    beta: Score,
     max_depth: i32,
     moves: &[ChessMove],
-    tt: Arc<Mutex<TransTable>>,
-    stop_now: Arc<AtomicBool>,
+    context: &mut SearchContext,
 ) -> Vec<MoveEval> {
     // Returns moves in best to worst order
     let mut scores: Vec<MoveEval> = vec![];
-    let mut value = -f32::INFINITY;
 
     for (i, possible_move) in moves.iter().enumerate() {
         debug!("Evaluating {}/{} moves", i + 1, moves.len());
@@ -216,29 +629,32 @@ pub fn negamax_root(
 
         // Check if it's a terminal node
         if new_board.status() == chess::BoardStatus::Checkmate {
-            // Return 10000 and +/- for how close to checkmate it is
             let score = MoveEval {
                 chess_move: *possible_move,
-                eval: 10000.0,
+                eval: Score::mate_in(1),
             };
             scores.push(score);
             break;
         } else if new_board.status() == chess::BoardStatus::Stalemate {
             let score = MoveEval {
                 chess_move: *possible_move,
-                eval: -1000.0,
+                eval: Score::DRAW,
             };
             scores.push(score);
         } else {
-            value = -negamax(
-                new_board,
-                max_depth,
-                max_depth - 1,
-                -beta,
-                -alpha,
-                tt.clone(),
-                stop_now.clone(),
-            );
+            let extension = context.extension(&board, &new_board, *possible_move, 0, false);
+            let depth = max_depth + extension;
+
+            context.history.push(&board, *possible_move);
+            context.set_capture_square(0, &board, Some(*possible_move));
+            context.extensions += extension;
+            let value = if i == 0 {
+                -negamax(new_board, depth - 1, 1, -beta, -alpha, context)
+            } else {
+                scout(new_board, depth, 0, 0, alpha, beta, context)
+            };
+            context.extensions -= extension;
+            context.history.pop();
 
             let score = MoveEval {
                 chess_move: *possible_move,
@@ -247,7 +663,7 @@ pub fn negamax_root(
 
             scores.push(score);
 
-            alpha = f32::max(alpha, value);
+            alpha = alpha.max(value);
 
             if alpha >= beta {
                 break;
@@ -261,102 +677,194 @@ pub fn negamax_root(
     scores
 }
 
-fn negamax(
+pub(crate) fn negamax(
     board: chess::Board,
-    max_depth: i32,
     depth_left: i32,
-    mut alpha: f32,
-    mut beta: f32,
-    tt: Arc<Mutex<TransTable>>,
-    stop_now: Arc<AtomicBool>,
-) -> f32 {
+    ply: i32,
+    mut alpha: Score,
+    mut beta: Score,
+    context: &mut SearchContext,
+) -> Score {
     let alpha_original = alpha;
+    let mut hash_move = None;
+    let null_move_allowed = !std::mem::take(&mut context.no_null_move);
+
+    // Draws depend on how the position was reached, which the table knows nothing about
+    if ply > 0 && context.history.is_draw(&board, context.search_start) {
+        return Score::DRAW;
+    }
+
+    let tt_entry_unwrapped = context.tt.get(board.get_hash());
+
+    if let Some(entry) = &tt_entry_unwrapped {
+        // Even a shallow entry knows a good move to try first
+        hash_move = entry.best_move;
+
+        if entry.depth >= depth_left {
+            let eval = entry.eval.to_search(ply);
+
+            if entry.flag == Flag::Exact {
+                return eval;
+            } else if entry.flag == Flag::Lowerbound {
+                alpha = alpha.max(eval);
+            } else if entry.flag == Flag::Upperbound {
+                beta = beta.min(eval);
+            }
 
-    let tt_entry = tt.lock().unwrap();
-    let tt_entry_unwrapped = tt_entry.tt.get(&board.get_hash());
-
-    match tt_entry_unwrapped {
-        Some(entry) => {
-            if entry.depth >= depth_left {
-                if entry.flag == Flag::Exact {
-                    return entry.eval;
-                } else if entry.flag == Flag::Lowerbound {
-                    alpha = f32::max(alpha, entry.eval);
-                } else if entry.flag == Flag::Upperbound {
-                    beta = f32::min(beta, entry.eval);
-                }
-
-                if alpha >= beta {
-                    return entry.eval;
-                }
+            if alpha >= beta {
+                return eval;
             }
         }
-        None => {}
     }
 
-    // Unlock asap
-    drop(tt_entry);
-
     // Check for checkmate first before transposition tables
     let current_board_status = board.status();
     if current_board_status == chess::BoardStatus::Checkmate {
-        // Return 1000 and +/- for how close to checkmate it is
-        if board.side_to_move() == chess::Color::White {
-            return (10000 - (max_depth - depth_left)) as f32;
-        } else {
-            return (-10000 + (max_depth - depth_left)) as f32;
-        }
+        // The side to move is mated, the later the better
+        return Score::mated_in(ply);
+    } else if current_board_status == chess::BoardStatus::Stalemate {
+        return Score::DRAW;
     }
 
-    // Negamax algorithm requires that evaluations be returned relative to the side being evaluated
+    // Don't stop in the middle of a capture sequence, resolve it first
     if depth_left == 0 {
-        if board.side_to_move() == chess::Color::White {
-            return evaluate::evaluate(board);
-        } else {
-            return -evaluate::evaluate(board);
-        }
+        return quiescence(board, ply, alpha, beta, context);
     }
 
-    // Eventually use algorithm to sort them by potential to save time
-    let possible_moves = MoveGen::new_legal(&board);
-    // Use fail soft variation
-    let mut value = -f32::INFINITY;
+    let in_check = board.checkers().popcnt() > 0;
+    // Nothing static can be trusted while in check
+    let static_eval = if in_check {
+        None
+    } else {
+        Some(evaluate_relative(board, &mut context.pawn_table))
+    };
 
-    if current_board_status == chess::BoardStatus::Stalemate {
-        // Avoid stalemate at all costs but at less cost than checkmate
-        if board.side_to_move() == chess::Color::White {
-            value = (-1000 + (max_depth - depth_left)) as f32;
-        } else {
-            value = (1000 - (max_depth - depth_left)) as f32;
+    if let Some(static_eval) = static_eval.filter(|_| ply > 0) {
+        // Reverse futility pruning, also called static null move pruning. Close to the leaves
+        // a position that is far above beta even after giving up a margin won't drop below it.
+        if depth_left <= REVERSE_FUTILITY_DEPTH
+            && !beta.is_mate()
+            && static_eval - Score::cp(REVERSE_FUTILITY_MARGIN * depth_left) >= beta
+        {
+            return static_eval;
+        }
+
+        // Razoring, a position that is far below alpha with only a ply or two left can only come
+        // back with a capture, so the captures are all that is searched. A pawn about to promote
+        // makes quiet threats the captures don't see, so those positions are searched normally.
+        if (depth_left as usize) < RAZOR_MARGINS.len()
+            && !alpha.is_mate()
+            && !has_advanced_pawn(&board)
+            && static_eval + Score::cp(RAZOR_MARGINS[depth_left as usize]) < alpha
+        {
+            let value = quiescence(board, ply, alpha, beta, context);
+            if depth_left == 1 || value <= alpha {
+                return value;
+            }
+        }
+
+        if null_move_allowed && depth_left >= 2 {
+            if let Some(value) =
+                null_move_cutoff(board, depth_left, ply, beta, static_eval, context)
+            {
+                return value;
+            }
         }
     }
 
-    for possible_move in possible_moves {
-        if stop_now.load(std::sync::atomic::Ordering::Relaxed) {
+    // Futility pruning, at frontier nodes quiet moves can't make up for being this far below alpha
+    let futility_bound = static_eval
+        .filter(|_| (depth_left as usize) < FUTILITY_MARGINS.len() && !alpha.is_mate())
+        .map(|static_eval| static_eval + Score::cp(FUTILITY_MARGINS[depth_left as usize]))
+        .filter(|bound| *bound <= alpha);
+
+    // Only worth the extra search deep in the tree, and never at the root of a helper thread,
+    // which has no move to extend anyway
+    let singular_move = match &tt_entry_unwrapped {
+        Some(entry) if ply > 0 && depth_left >= SINGULAR_MIN_DEPTH => {
+            singular_move(board, entry, depth_left, ply, context)
+        }
+        _ => None,
+    };
+
+    let possible_moves = context.ordering.order_moves(&board, hash_move, ply as usize);
+    // Use fail soft variation
+    let mut value = -Score::INFINITY;
+    let mut best_move = None;
+    let mut quiet_moves_searched = 0;
+
+    for (move_number, possible_move) in possible_moves.into_iter().enumerate() {
+        if context.should_stop() {
             break;
         }
 
-        // Check if it's a terminal node
-        value = f32::max(
-            value,
-            -negamax(
-                board.make_move_new(possible_move),
-                max_depth,
-                depth_left - 1,
-                -beta,
-                -alpha,
-                tt.clone(),
-                stop_now.clone(),
-            ),
+        let new_board = board.make_move_new(possible_move);
+        let quiet = is_quiet(&board, &new_board, possible_move);
+        let extension = context.extension(
+            &board,
+            &new_board,
+            possible_move,
+            ply,
+            singular_move == Some(possible_move),
         );
 
-        alpha = f32::max(alpha, value);
+        if late_move_pruned(quiet, in_check, depth_left, quiet_moves_searched, value) {
+            continue;
+        }
+
+        if let Some(bound) = futility_bound.filter(|_| quiet && move_number > 0) {
+            // The move can't be better than the bound, which is still below alpha
+            value = value.max(bound);
+            continue;
+        }
+
+        let reduction = if extension == 0 {
+            let killer = context.ordering.is_killer(possible_move, ply as usize);
+            late_move_reduction(quiet, in_check, depth_left, move_number, killer)
+        } else {
+            0
+        };
+
+        let depth = depth_left + extension;
+
+        context.history.push(&board, possible_move);
+        context.set_capture_square(ply, &board, Some(possible_move));
+        context.extensions += extension;
+        let move_value = if move_number == 0 {
+            -negamax(new_board, depth - 1, ply + 1, -beta, -alpha, context)
+        } else {
+            scout(new_board, depth, reduction, ply, alpha, beta, context)
+        };
+        context.extensions -= extension;
+        context.history.pop();
+
+        if quiet {
+            quiet_moves_searched += 1;
+        }
+
+        if move_value > value {
+            value = move_value;
+            best_move = Some(possible_move);
+        }
+
+        alpha = alpha.max(value);
 
         if alpha >= beta {
+            // Quiet moves that refute a position are likely to refute its siblings too
+            if !move_ordering::is_tactical(&board, possible_move) {
+                context
+                    .ordering
+                    .record_cutoff(&board, possible_move, depth_left, ply as usize);
+            }
             break;
         }
     }
 
+    // An aborted node hasn't seen all of its moves, so its value must not end up in the table
+    if context.stop_now.load(std::sync::atomic::Ordering::Relaxed) {
+        return value;
+    }
+
     let flag: Flag;
     if value <= alpha_original {
         flag = Flag::Upperbound;
@@ -369,10 +877,317 @@ fn negamax(
     let tt_entry = TransTableEntry {
         depth: depth_left,
         flag,
-        eval: value,
+        eval: value.to_tt(ply),
+        best_move,
+    };
+
+    context.tt.add_entry(board, tt_entry);
+
+    value
+}
+
+/// Whether a move neither changes the material nor forces a reply, only those are ever reduced or
+/// pruned
+pub(crate) fn is_quiet(board: &Board, new_board: &Board, chess_move: ChessMove) -> bool {
+    !move_ordering::is_tactical(board, chess_move) && new_board.checkers().popcnt() == 0
+}
+
+/// Late move pruning, near the leaves the quiet moves ordered last are hardly ever good, so only
+/// the first few of them are searched. Not while getting mated, when any move might be the one
+/// that escapes.
+pub(crate) fn late_move_pruned(
+    quiet: bool,
+    in_check: bool,
+    depth_left: i32,
+    quiet_moves_searched: usize,
+    best_value: Score,
+) -> bool {
+    quiet
+        && !in_check
+        && (depth_left as usize) < LMP_MOVE_COUNTS.len()
+        && quiet_moves_searched >= LMP_MOVE_COUNTS[depth_left as usize]
+        && !(best_value.is_mate() && best_value < Score::DRAW)
+}
+
+/// Late move reductions, how many plies shallower a move is searched. Quiet moves ordered late
+/// are unlikely to be good, but killers have refuted a sibling and are worth the full depth.
+pub(crate) fn late_move_reduction(
+    quiet: bool,
+    in_check: bool,
+    depth_left: i32,
+    move_number: usize,
+    killer: bool,
+) -> i32 {
+    if !quiet
+        || in_check
+        || killer
+        || depth_left < LMR_MIN_DEPTH
+        || move_number < LMR_FULL_DEPTH_MOVES
+    {
+        return 0;
+    }
+
+    REDUCTIONS[(depth_left as usize).min(63)][move_number.min(63)].min(depth_left - 2)
+}
+
+/// Singular extensions: when the hash move is a lot better than every other move, the position
+/// hinges on it and it's worth looking at more closely. To find out, the other moves are searched
+/// with half the depth against a bound a bit below the score the table has for the hash move.
+/// Returns the hash move if none of them gets there.
+pub(crate) fn singular_move(
+    board: Board,
+    entry: &TransTableEntry,
+    depth_left: i32,
+    ply: i32,
+    context: &mut SearchContext,
+) -> Option<ChessMove> {
+    let hash_move = entry.best_move.filter(|hash_move| board.legal(*hash_move))?;
+    let tt_score = entry.eval.to_search(ply);
+
+    // The score has to be reliable and at least as good as it says
+    if entry.depth < depth_left - SINGULAR_TT_DEPTH_MARGIN
+        || !matches!(entry.flag, Flag::Lowerbound | Flag::Exact)
+        || tt_score.is_mate()
+    {
+        return None;
+    }
+
+    let singular_beta = tt_score - Score::cp(SINGULAR_MARGIN_PER_PLY * depth_left);
+
+    for chess_move in MoveGen::new_legal(&board) {
+        if chess_move == hash_move {
+            continue;
+        }
+
+        context.history.push(&board, chess_move);
+        context.set_capture_square(ply, &board, Some(chess_move));
+        let value = -negamax(
+            board.make_move_new(chess_move),
+            depth_left / 2 - 1,
+            ply + 1,
+            -singular_beta,
+            -singular_beta + Score::cp(1),
+            context,
+        );
+        context.history.pop();
+
+        if value >= singular_beta || context.should_stop() {
+            return None;
+        }
+    }
+
+    Some(hash_move)
+}
+
+/// Principal variation search of a move that isn't the first one of its node. The first move is
+/// most likely the best, so the others only have to be shown to be worse, which a null window
+/// around alpha does cheaper than the full window. Only a move that beats alpha after all is
+/// searched again with the full window. Late moves may also be reduced, when a reduced search
+/// beats alpha the move gets the full depth first.
+pub(crate) fn scout(
+    new_board: Board,
+    depth_left: i32,
+    reduction: i32,
+    ply: i32,
+    alpha: Score,
+    beta: Score,
+    context: &mut SearchContext,
+) -> Score {
+    let null_window = -alpha - Score::cp(1);
+    let mut value = -negamax(
+        new_board,
+        depth_left - 1 - reduction,
+        ply + 1,
+        null_window,
+        -alpha,
+        context,
+    );
+
+    if reduction > 0 && value > alpha {
+        value = -negamax(new_board, depth_left - 1, ply + 1, null_window, -alpha, context);
+    }
+
+    if value > alpha && value < beta {
+        value = -negamax(new_board, depth_left - 1, ply + 1, -beta, -alpha, context);
+    }
+
+    value
+}
+
+/// Null move pruning: let the opponent move twice in a row with a shallower search. If that still
+/// fails high, the position is so good that a real move would too, and the node can be cut off.
+/// That's wrong in zugzwang, where any move makes things worse, so it's not tried when in check or
+/// with only pawns left, and deep cutoffs are verified by a normal search without null moves.
+pub(crate) fn null_move_cutoff(
+    board: Board,
+    depth_left: i32,
+    ply: i32,
+    beta: Score,
+    static_eval: Score,
+    context: &mut SearchContext,
+) -> Option<Score> {
+    // A null move can't prove a mate, and in check there is no null move
+    if beta.is_mate() || static_eval < beta || !has_non_pawn_material(&board) {
+        return None;
+    }
+    let null_board = board.null_move()?;
+
+    let reduction = if depth_left > NULL_MOVE_DEEP_DEPTH {
+        NULL_MOVE_REDUCTION + 1
+    } else {
+        NULL_MOVE_REDUCTION
     };
+    let null_depth = (depth_left - 1 - reduction).max(0);
+
+    context.history.push_null(&null_board);
+    context.set_capture_square(ply, &board, None);
+    context.no_null_move = true;
+    let value = -negamax(
+        null_board,
+        null_depth,
+        ply + 1,
+        -beta,
+        -beta + Score::cp(1),
+        context,
+    );
+    context.no_null_move = false;
+    context.history.pop();
+
+    if value < beta || context.stop_now.load(std::sync::atomic::Ordering::Relaxed) {
+        return None;
+    }
+
+    if depth_left >= NULL_MOVE_VERIFICATION_DEPTH {
+        context.no_null_move = true;
+        let verified = negamax(board, null_depth, ply, beta - Score::cp(1), beta, context);
+        context.no_null_move = false;
+
+        if verified < beta {
+            return None;
+        }
+    }
+
+    // Mates found behind a null move aren't real
+    Some(if value.is_mate() { beta } else { value })
+}
+
+/// Whether the side to move has anything besides pawns and the king, zugzwang is common otherwise
+fn has_non_pawn_material(board: &Board) -> bool {
+    let pawns_and_king = board.pieces(chess::Piece::Pawn) | board.pieces(chess::Piece::King);
+    (board.color_combined(board.side_to_move()) & !pawns_and_king).popcnt() > 0
+}
+
+// Pawns on the sixth or seventh rank of the side to move
+pub(crate) fn has_advanced_pawn(board: &Board) -> bool {
+    let color = board.side_to_move();
+    (board.pieces(Piece::Pawn) & board.color_combined(color))
+        .into_iter()
+        .any(|square| pawns::relative_rank(square, color) >= 5)
+}
+
+/// Searches captures only until the position is quiet, so the static evaluation is never taken in
+/// the middle of an exchange. When in check, every evasion is searched instead.
+pub(crate) fn quiescence(
+    board: Board,
+    ply: i32,
+    mut alpha: Score,
+    beta: Score,
+    context: &mut SearchContext,
+) -> Score {
+    // Captures can leave too little material to mate, and no amount of it helps then
+    if draw::is_insufficient_material(&board) {
+        return Score::DRAW;
+    }
+
+    let in_check = board.checkers().popcnt() > 0;
+    let mut moves = MoveGen::new_legal(&board);
+    // Use fail soft variation
+    let mut value;
+    let mut stand_pat = -Score::INFINITY;
 
-    tt.lock().unwrap().add_entry(board, tt_entry);
+    if in_check {
+        if moves.len() == 0 {
+            // Checkmated, prefer being mated as late as possible
+            return Score::mated_in(ply);
+        }
+
+        value = -Score::INFINITY;
+    } else {
+        // The side to move can usually do at least as well as the static evaluation by not
+        // capturing at all
+        stand_pat = evaluate_relative(board, &mut context.pawn_table);
+        if stand_pat >= beta {
+            return stand_pat;
+        }
+
+        alpha = alpha.max(stand_pat);
+        value = stand_pat;
+
+        let mut targets = *board.color_combined(!board.side_to_move());
+        if let Some(en_passant) = board.en_passant() {
+            // The crate stores the square of the pawn that can be taken, not the one moved to
+            targets |= chess::BitBoard::from_square(
+                en_passant
+                    .forward(board.side_to_move())
+                    .expect("En passant square on the last rank"),
+            );
+        }
+        moves.set_iterator_mask(targets);
+    }
+
+    let mut captures: Vec<ChessMove> = moves.collect();
+    captures.sort_by_key(|capture| -move_ordering::mvv_lva(&board, *capture));
+
+    for capture in captures {
+        if context.should_stop() {
+            break;
+        }
+
+        // Delta pruning, skip captures that can't bring the score back up to alpha even with a
+        // generous margin. Promotions can swing the score too much to be pruned this way.
+        if !in_check && capture.get_promotion().is_none() {
+            let captured_value = move_ordering::captured_piece(&board, capture)
+                .map_or(Score::DRAW, |piece| {
+                    Score::cp(evaluate::PIECE_VALUES[piece.to_index()])
+                });
+
+            if stand_pat + captured_value + DELTA_MARGIN < alpha {
+                continue;
+            }
+
+            // Captures that lose material in the exchange hardly ever help
+            if !see::see_ge(&board, capture, 0) {
+                continue;
+            }
+        }
+
+        value = value.max(-quiescence(
+            board.make_move_new(capture),
+            ply + 1,
+            -beta,
+            -alpha,
+            context,
+        ));
+
+        alpha = alpha.max(value);
+
+        if alpha >= beta {
+            break;
+        }
+    }
 
     value
 }
+
+/// Negamax algorithm requires that evaluations be returned relative to the side being evaluated
+fn evaluate_relative(board: Board, pawn_table: &mut PawnTable) -> Score {
+    if board.side_to_move() == chess::Color::White {
+        evaluate::evaluate(board, pawn_table)
+    } else {
+        -evaluate::evaluate(board, pawn_table)
+    }
+}
+
+fn is_winning_mate(score: Score) -> bool {
+    score.is_mate() && score > Score::DRAW
+}
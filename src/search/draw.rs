@@ -0,0 +1,120 @@
+//! Draws by rule. A `Board` alone doesn't know how it was reached, so the search carries the
+//! game's history along: the hash of every position since the last capture or pawn move together
+//! with the halfmove clock. That's enough to spot repetitions and the fifty move rule.
+
+use chess::{BitBoard, Board, BoardStatus, ChessMove, Piece, EMPTY};
+
+use super::move_ordering::captured_piece;
+
+// The fifty move rule counts moves of both sides, so a hundred halfmoves
+const FIFTY_MOVE_LIMIT: u32 = 100;
+// A1 and every other square of its color
+const DARK_SQUARES: BitBoard = BitBoard(0xAA55_AA55_AA55_AA55);
+
+/// Positions of the game up to and including the current one
+#[derive(Clone, Debug)]
+pub struct GameHistory {
+    // Hash of each position and the halfmove clock when it was reached
+    positions: Vec<(u64, u32)>,
+}
+
+impl GameHistory {
+    /// Starts the history at `board`, with the halfmove clock of its FEN
+    pub fn new(board: &Board, halfmove_clock: u32) -> Self {
+        GameHistory {
+            positions: vec![(board.get_hash(), halfmove_clock)],
+        }
+    }
+
+    /// Records the position reached by playing `chess_move` on `board`, which has to be the
+    /// current position
+    pub fn push(&mut self, board: &Board, chess_move: ChessMove) {
+        let irreversible = board.piece_on(chess_move.get_source()) == Some(Piece::Pawn)
+            || captured_piece(board, chess_move).is_some();
+        let halfmove_clock = if irreversible {
+            0
+        } else {
+            self.halfmove_clock() + 1
+        };
+
+        self.positions
+            .push((board.make_move_new(chess_move).get_hash(), halfmove_clock));
+    }
+
+    /// Records a null move that led to `board`. A position before it can't come up again after
+    /// it, so it's treated like an irreversible move.
+    pub fn push_null(&mut self, board: &Board) {
+        self.positions.push((board.get_hash(), 0));
+    }
+
+    /// Takes back the last `push` or `push_null`
+    pub fn pop(&mut self) {
+        self.positions.pop();
+    }
+
+    pub fn len(&self) -> usize {
+        self.positions.len()
+    }
+
+    pub fn halfmove_clock(&self) -> u32 {
+        self.positions.last().map_or(0, |(_, clock)| *clock)
+    }
+
+    /// Whether the current position is a draw by repetition. Once the search repeats a position
+    /// it could repeat it again, so a single repetition inside the search counts already. The
+    /// search started with `search_start` positions in the history, a position that only occurred
+    /// before that has to have been there twice, like the threefold rule says.
+    pub fn is_repetition(&self, search_start: usize) -> bool {
+        let (hash, halfmove_clock) = match self.positions.last() {
+            Some(position) => *position,
+            None => return false,
+        };
+        let current = self.positions.len() - 1;
+        // Nothing before the last irreversible move can be the same position
+        let earliest = current.saturating_sub(halfmove_clock as usize);
+        let mut repetitions = 0;
+
+        // Only positions with the same side to move can be equal
+        for index in (earliest..current.saturating_sub(1)).rev().step_by(2) {
+            if self.positions[index].0 == hash {
+                if index + 1 >= search_start {
+                    return true;
+                }
+
+                repetitions += 1;
+                if repetitions >= 2 {
+                    return true;
+                }
+            }
+        }
+
+        false
+    }
+
+    /// Draws by repetition, by the fifty move rule (unless the last move mated) and by
+    /// insufficient material
+    pub fn is_draw(&self, board: &Board, search_start: usize) -> bool {
+        self.is_repetition(search_start)
+            || (self.halfmove_clock() >= FIFTY_MOVE_LIMIT
+                && board.status() != BoardStatus::Checkmate)
+            || is_insufficient_material(board)
+    }
+}
+
+/// Positions where neither side can possibly mate: bare kings, a single minor piece, or only
+/// bishops that all stand on squares of the same color
+pub fn is_insufficient_material(board: &Board) -> bool {
+    let heavy = board.pieces(Piece::Pawn) | board.pieces(Piece::Rook) | board.pieces(Piece::Queen);
+    if heavy != EMPTY {
+        return false;
+    }
+
+    let knights = *board.pieces(Piece::Knight);
+    let bishops = *board.pieces(Piece::Bishop);
+
+    if (knights | bishops).popcnt() <= 1 {
+        return true;
+    }
+
+    knights == EMPTY && (bishops & DARK_SQUARES == EMPTY || bishops & !DARK_SQUARES == EMPTY)
+}
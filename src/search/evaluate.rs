@@ -1,18 +1,42 @@
-//! The super weird evaluation function. It counts up pieces for each side, then compare number of possible moves.
-//! It weights the bishop slightly more heavily than
-//! the knight, which is generally true for almost all cases. It used to be 3.5, but I figured
-//! that the possible moves decrease from the loss of a bishop may compensate for that. Each additional move would add 0.1
-//! The randomness is added so that moves with the same eval can be chosen randomly.
+//! The super weird evaluation function. It counts up pieces for each side and where they stand,
+//! once for the middlegame and once for the endgame. Then it blends the two by how much material
+//! is left, so a king that hides in the corner early on walks to the center once the queens are
+//! gone without the eval jumping around when the last piece comes off. On top of that come the
+//! pawn structure, king safety, and mobility, which compares the number of squares the pieces of
+//! each side can go to.
+//! It weights the bishop slightly more heavily than the knight, which is generally true for
+//! almost all cases.
 
-use chess::{Color};
+use std::ops::{Add, AddAssign, Mul, Sub};
+
+use chess::{Board, Color, ALL_PIECES};
+
+use pawns::PawnTable;
+
+use super::score::Score;
+
+pub(crate) mod king_safety;
+pub(crate) mod mobility;
+pub(crate) mod pawns;
 
 // This  implements Piece Square Tables (PSQT) for each piece type. The
 // PSQT's are written from White's point of view, as if looking at a chess
-// diagram, with A1 on the lower left corner.
-// Taken from https://github.com/mvanthoor/rustic/blob/master/src/evaluation/psqt.rs
+// diagram, with A8 on the upper left corner.
+// The middlegame ones are taken from https://github.com/mvanthoor/rustic/blob/master/src/evaluation/psqt.rs
 
 type Psqt = [i32; 64];
-const PIECE_TABLE_ARRAY: [Psqt; 6] = [PAWN_MG, KNIGHT_MG, BISHOP_MG, ROOK_MG, QUEEN_MG, KING_MG];
+
+/// Material value of each piece in centipawns, in the order of pawn, knight, bishop, rook, queen, king
+pub const PIECE_VALUES: [i32; 6] = [100, 320, 330, 500, 900, 0];
+// Pawns get more important in the endgame and minor pieces a bit less
+const PIECE_VALUES_EG: [i32; 6] = [120, 300, 320, 530, 950, 0];
+
+/// Phase of the starting position. Every knight and bishop counts 1, rooks 2 and queens 4.
+pub const MAX_PHASE: i32 = 24;
+const PHASE_WEIGHTS: [i32; 6] = [0, 1, 1, 2, 4, 0];
+
+const PSQT_MG: [Psqt; 6] = [PAWN_MG, KNIGHT_MG, BISHOP_MG, ROOK_MG, QUEEN_MG, KING_MG];
+const PSQT_EG: [Psqt; 6] = [PAWN_EG, KNIGHT_EG, BISHOP_EG, ROOK_EG, QUEEN_EG, KING_EG];
 
 #[rustfmt::skip]
 const KING_MG: Psqt = [
@@ -27,7 +51,7 @@ const KING_MG: Psqt = [
 ];
 
 #[rustfmt::skip]
-const KING_MG_ENDGAME: Psqt = [
+const KING_EG: Psqt = [
     -50,-40,-30,-20,-20,-30,-40,-50,
     -30,-20,-10,  0,  0,-10,-20,-30,
     -30,-10, 20, 30, 30, 20,-10,-30,
@@ -99,110 +123,162 @@ const PAWN_MG: Psqt = [
 ];
 
 #[rustfmt::skip]
-pub const FLIP: [usize; 128] = [
-    56, 57, 58, 59, 60, 61, 62, 63,
-    48, 49, 50, 51, 52, 53, 54, 55,
-    40, 41, 42, 43, 44, 45, 46, 47,
-    32, 33, 34, 35, 36, 37, 38, 39,
-    24, 25, 26, 27, 28, 29, 30, 31,
-    16, 17, 18, 19, 20, 21, 22, 23,
-     8,  9, 10, 11, 12, 13, 14, 15,
-     0,  1,  2,  3,  4,  5,  6,  7,
-     0,  1,  2,  3,  4,  5,  6,  7,
-     8,  9, 10, 11, 12, 13, 14, 15,
-    16, 17, 18, 19, 20, 21, 22, 23,
-    24, 25, 26, 27, 28, 29, 30, 31,
-    32, 33, 34, 35, 36, 37, 38, 39,
-    40, 41, 42, 43, 44, 45, 46, 47,
-    48, 49, 50, 51, 52, 53, 54, 55,
-    56, 57, 58, 59, 60, 61, 62, 63,
+const QUEEN_EG: Psqt = [
+    -20,-10,-10, -5, -5,-10,-10,-20,
+    -10,  0,  5,  5,  5,  5,  0,-10,
+    -10,  5, 10, 10, 10, 10,  5,-10,
+     -5,  5, 10, 15, 15, 10,  5, -5,
+     -5,  5, 10, 15, 15, 10,  5, -5,
+    -10,  5, 10, 10, 10, 10,  5,-10,
+    -10,  0,  5,  5,  5,  5,  0,-10,
+    -20,-10,-10, -5, -5,-10,-10,-20
+];
+
+#[rustfmt::skip]
+const ROOK_EG: Psqt = [
+    10, 10, 10, 10, 10, 10, 10, 10,
+    15, 15, 15, 15, 15, 15, 15, 15,
+     0,  0,  0,  0,  0,  0,  0,  0,
+     0,  0,  0,  0,  0,  0,  0,  0,
+     0,  0,  0,  0,  0,  0,  0,  0,
+     0,  0,  0,  0,  0,  0,  0,  0,
+     0,  0,  0,  0,  0,  0,  0,  0,
+     0,  0,  0,  0,  0,  0,  0,  0
+];
+
+#[rustfmt::skip]
+const BISHOP_EG: Psqt = [
+    -20,-10,-10,-10,-10,-10,-10,-20,
+    -10,  0,  0,  0,  0,  0,  0,-10,
+    -10,  0, 10, 10, 10, 10,  0,-10,
+    -10,  0, 10, 15, 15, 10,  0,-10,
+    -10,  0, 10, 15, 15, 10,  0,-10,
+    -10,  0, 10, 10, 10, 10,  0,-10,
+    -10,  0,  0,  0,  0,  0,  0,-10,
+    -20,-10,-10,-10,-10,-10,-10,-20,
+];
+
+#[rustfmt::skip]
+const KNIGHT_EG: Psqt = [
+    -50,-40,-30,-30,-30,-30,-40,-50,
+    -40,-20,  0,  0,  0,  0,-20,-40,
+    -30,  0, 10, 15, 15, 10,  0,-30,
+    -30,  5, 15, 20, 20, 15,  5,-30,
+    -30,  5, 15, 20, 20, 15,  5,-30,
+    -30,  0, 10, 15, 15, 10,  0,-30,
+    -40,-20,  0,  0,  0,  0,-20,-40,
+    -50,-40,-30,-30,-30,-30,-40,-50,
 ];
 
+// Pawns are worth more the closer they get to promoting, no matter the file
+#[rustfmt::skip]
+const PAWN_EG: Psqt = [
+     0,  0,  0,  0,  0,  0,  0,  0,
+    80, 80, 80, 80, 80, 80, 80, 80,
+    50, 50, 50, 50, 50, 50, 50, 50,
+    30, 30, 30, 30, 30, 30, 30, 30,
+    15, 15, 15, 15, 15, 15, 15, 15,
+     5,  5,  5,  5,  5,  5,  5,  5,
+     0,  0,  0,  0,  0,  0,  0,  0,
+     0,  0,  0,  0,  0,  0,  0,  0
+];
+
+/// A term with separate middlegame and endgame values, blended by the game phase at the end
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct TaperedScore {
+    pub mg: i32,
+    pub eg: i32,
+}
+
+impl TaperedScore {
+    pub const fn new(mg: i32, eg: i32) -> Self {
+        TaperedScore { mg, eg }
+    }
+
+    /// Blends the two values, `MAX_PHASE` is pure middlegame and 0 pure endgame
+    pub fn taper(self, phase: i32) -> i32 {
+        (self.mg * phase + self.eg * (MAX_PHASE - phase)) / MAX_PHASE
+    }
+}
+
+impl Add for TaperedScore {
+    type Output = TaperedScore;
+
+    fn add(self, other: TaperedScore) -> TaperedScore {
+        TaperedScore::new(self.mg + other.mg, self.eg + other.eg)
+    }
+}
+
+impl AddAssign for TaperedScore {
+    fn add_assign(&mut self, other: TaperedScore) {
+        *self = *self + other;
+    }
+}
+
+impl Sub for TaperedScore {
+    type Output = TaperedScore;
+
+    fn sub(self, other: TaperedScore) -> TaperedScore {
+        TaperedScore::new(self.mg - other.mg, self.eg - other.eg)
+    }
+}
+
+impl Mul<i32> for TaperedScore {
+    type Output = TaperedScore;
+
+    fn mul(self, factor: i32) -> TaperedScore {
+        TaperedScore::new(self.mg * factor, self.eg * factor)
+    }
+}
+
+/// Static evaluation of the position in centipawns, positive when white is better. The pawn
+/// structure is looked up in `pawn_table` and only computed when it isn't in there yet.
 #[inline(always)]
-pub fn evaluate(board: chess::Board) -> f32 {
-    // In the order white, black
-    let mut color_eval: [f32; 2] = [0.0, 0.0];
-    let mut num_of_queens_white = 0;
-    let mut num_of_other_minors_white = 0;
-    let mut num_of_queens_black = 0;
-    let mut num_of_other_minors_black = 0;
-
-    // In the order of pawn, knight, bishop, root, queen, king
-    let piece_values: [f32; 6] = [100.0, 320.0, 330.0, 500.0, 900.0, 0.0];
-
-    for color in chess::ALL_COLORS {
-        let color_bitboard = board.color_combined(color);
-        let mut color_specific_eval: f32 = 0.0;
-
-        for (i, piece) in chess::ALL_PIECES.iter().enumerate() {
-            if color == Color::White {
-                if piece == &chess::Piece::Queen {
-                    num_of_queens_white += 1
-                } else if piece == &chess::Piece::Bishop
-                    || piece == &chess::Piece::Knight
-                    || piece == &chess::Piece::Rook
-                {
-                    num_of_other_minors_white += 1
-                }
-            } else {
-                if piece == &chess::Piece::Queen {
-                    num_of_queens_black += 1
-                } else if piece == &chess::Piece::Bishop
-                    || piece == &chess::Piece::Knight
-                    || piece == &chess::Piece::Rook
-                {
-                    num_of_other_minors_black += 1
-                }
-            }
-
-            let piece_bitboard = board.pieces(*piece);
-            // Looks for pieces of that type of that color
-            let num_of_pieces_of_type = piece_bitboard & color_bitboard;
-            color_specific_eval += num_of_pieces_of_type.popcnt() as f32 * piece_values[i];
-
-            if piece == &chess::Piece::King
-                && is_endgame(
-                    num_of_queens_white,
-                    num_of_queens_black,
-                    num_of_other_minors_white,
-                    num_of_other_minors_black,
-                )
-            {
-                let mut piece_int = num_of_pieces_of_type.0;
-                for _ in 0..piece_int.count_ones() {
-                    color_specific_eval += KING_MG_ENDGAME
-                        [FLIP[64 * color.to_index() + piece_int.leading_zeros() as usize]]
-                        as f32;
-                    piece_int ^= 1 << piece_int.trailing_zeros();
-                }
-            } else {
-                let mut piece_int = num_of_pieces_of_type.0;
-                for _ in 0..piece_int.count_ones() {
-                    color_specific_eval += PIECE_TABLE_ARRAY[i]
-                        [FLIP[64 * color.to_index() + piece_int.leading_zeros() as usize]]
-                        as f32;
-                    piece_int ^= 1 << piece_int.trailing_zeros();
-                }
-            }
-        }
-        if color == Color::Black {
-            color_eval[1] += color_specific_eval;
-        } else {
-            color_eval[0] += color_specific_eval;
+pub fn evaluate(board: Board, pawn_table: &mut PawnTable) -> Score {
+    let mut score =
+        material_and_psqt(&board, Color::White) - material_and_psqt(&board, Color::Black);
+    score += pawns::evaluate_pawns(&board, pawn_table);
+    score += king_safety::evaluate_king_safety(&board);
+    score += mobility::evaluate_mobility(&board);
+
+    Score::cp(score.taper(game_phase(&board)))
+}
+
+/// How much of the non-pawn material is still on the board, from `MAX_PHASE` in the starting
+/// position down to 0 when only kings and pawns are left. Promotions can push it over the
+/// maximum, so it's capped.
+pub fn game_phase(board: &Board) -> i32 {
+    let phase: i32 = ALL_PIECES
+        .iter()
+        .map(|piece| board.pieces(*piece).popcnt() as i32 * PHASE_WEIGHTS[piece.to_index()])
+        .sum();
+
+    phase.min(MAX_PHASE)
+}
+
+fn material_and_psqt(board: &Board, color: Color) -> TaperedScore {
+    let mut score = TaperedScore::default();
+
+    for piece in ALL_PIECES {
+        let index = piece.to_index();
+
+        for square in board.pieces(piece) & board.color_combined(color) {
+            let table_index = psqt_index(square.to_index(), color);
+            score += TaperedScore::new(
+                PIECE_VALUES[index] + PSQT_MG[index][table_index],
+                PIECE_VALUES_EG[index] + PSQT_EG[index][table_index],
+            );
         }
     }
 
-    color_eval[0] as f32 / 100.0 - color_eval[1] as f32 / 100.0
+    score
 }
 
-fn is_endgame(white_queen: i32, black_queen: i32, white_minor: i32, black_minor: i32) -> bool {
-    if white_queen == 0 && black_queen == 0 && white_minor <= 2 && black_minor <= 2 {
-        return true
-    } else if white_queen == 1 && black_queen == 0 && white_minor == 0 && black_minor == 0 {
-        return true
-    } else if white_queen == 0 && black_queen == 1 && white_minor == 0 && black_minor == 0 {
-        return true
-    } 
-    
-    false
+// The tables are written with A8 first, while square indices start at A1. Black reads them
+// mirrored vertically, which turns out to be the square index itself.
+fn psqt_index(square: usize, color: Color) -> usize {
+    match color {
+        Color::White => square ^ 56,
+        Color::Black => square,
+    }
 }
@@ -0,0 +1,115 @@
+//! King safety: the pawns in front of the king, enemy pawns storming towards it, open files next
+//! to it and how many enemy pieces bear down on the squares around it. All of it only matters
+//! while there is enough material left to mate, so the terms only have a middlegame value and
+//! fade out with the game phase.
+
+use chess::{
+    get_bishop_moves, get_file, get_king_moves, get_knight_moves, get_rook_moves, BitBoard, Board,
+    Color, File, Piece, Square, EMPTY,
+};
+
+use super::pawns::ranks_ahead;
+use super::TaperedScore;
+
+// Indexed by how many ranks the closest own pawn on a file is in front of the king. Further away
+// or no pawn at all leaves a hole in the shield.
+const SHIELD: [i32; 3] = [0, 15, 8];
+const SHIELD_MISSING: i32 = -15;
+// Indexed by how many ranks the closest enemy pawn on a file is in front of the king. One rank
+// away it's usually stuck on the shield pawn and not that dangerous.
+const STORM: [i32; 5] = [0, -5, -20, -10, -5];
+const SEMI_OPEN_FILE: i32 = -15;
+const OPEN_FILE: i32 = -10;
+// Attack units for every square of the king zone a piece hits, in the order of pawn, knight,
+// bishop, rook, queen, king
+const ATTACK_UNITS: [i32; 6] = [0, 20, 20, 40, 80, 0];
+// Percentage of the attack units that counts, by the number of pieces attacking the zone. A
+// single piece can't do much on its own.
+const ATTACKER_WEIGHT: [i32; 8] = [0, 0, 50, 75, 88, 94, 97, 99];
+
+/// King safety of the position, positive when white's king is safer
+pub fn evaluate_king_safety(board: &Board) -> TaperedScore {
+    TaperedScore::new(
+        king_safety(board, Color::White) - king_safety(board, Color::Black),
+        0,
+    )
+}
+
+fn king_safety(board: &Board, color: Color) -> i32 {
+    let king = board.king_square(color);
+    pawn_cover(board, king, color) - king_attack(board, king, color)
+}
+
+fn pawn_cover(board: &Board, king: Square, color: Color) -> i32 {
+    let pawns = board.pieces(Piece::Pawn);
+    let own_pawns = pawns & board.color_combined(color);
+    let enemy_pawns = pawns & board.color_combined(!color);
+    let ahead = ranks_ahead(king, color);
+    let king_file = king.get_file().to_index();
+    let mut score = 0;
+
+    for file_index in king_file.saturating_sub(1)..=(king_file + 1).min(7) {
+        let file = get_file(File::from_index(file_index));
+
+        score += match nearest_pawn_distance(own_pawns & file & ahead, king, color) {
+            Some(distance) if distance < SHIELD.len() => SHIELD[distance],
+            _ => SHIELD_MISSING,
+        };
+
+        if let Some(distance) = nearest_pawn_distance(enemy_pawns & file & ahead, king, color) {
+            score += STORM.get(distance).copied().unwrap_or(0);
+        }
+
+        if own_pawns & file == EMPTY {
+            score += SEMI_OPEN_FILE;
+            if enemy_pawns & file == EMPTY {
+                score += OPEN_FILE;
+            }
+        }
+    }
+
+    score
+}
+
+// Ranks between the king and the pawn closest to it in front
+fn nearest_pawn_distance(pawns: BitBoard, king: Square, color: Color) -> Option<usize> {
+    if pawns == EMPTY {
+        return None;
+    }
+
+    let nearest = match color {
+        Color::White => pawns.0.trailing_zeros(),
+        Color::Black => 63 - pawns.0.leading_zeros(),
+    };
+
+    Some((nearest as usize / 8).abs_diff(king.get_rank().to_index()))
+}
+
+/// Attack units model: every enemy piece that attacks the zone around the king adds units for
+/// each square it hits, and the more pieces join the attack the more of those units count
+fn king_attack(board: &Board, king: Square, color: Color) -> i32 {
+    let zone = get_king_moves(king) | BitBoard::from_square(king);
+    let occupied = *board.combined();
+    let mut attackers = 0;
+    let mut units = 0;
+
+    for square in *board.color_combined(!color) & !board.pieces(Piece::Pawn) {
+        let piece = board
+            .piece_on(square)
+            .expect("Square of the color bitboard is empty");
+        let attacks = match piece {
+            Piece::Knight => get_knight_moves(square),
+            Piece::Bishop => get_bishop_moves(square, occupied),
+            Piece::Rook => get_rook_moves(square, occupied),
+            Piece::Queen => get_bishop_moves(square, occupied) | get_rook_moves(square, occupied),
+            _ => EMPTY,
+        } & zone;
+
+        if attacks != EMPTY {
+            attackers += 1;
+            units += ATTACK_UNITS[piece.to_index()] * attacks.popcnt() as i32;
+        }
+    }
+
+    units * ATTACKER_WEIGHT[attackers.min(ATTACKER_WEIGHT.len() - 1)] / 100
+}
@@ -0,0 +1,57 @@
+//! Mobility: pieces that can go to more squares are worth more. Squares covered by enemy pawns
+//! don't count, since a piece that goes there is simply taken.
+
+use chess::{
+    get_bishop_moves, get_knight_moves, get_pawn_attacks, get_rook_moves, BitBoard, Board, Color,
+    Piece, EMPTY,
+};
+
+use super::TaperedScore;
+
+// Bonus per reachable square, in the order of pawn, knight, bishop, rook, queen, king. Rooks and
+// queens need open lines more once the board empties.
+const MOBILITY_MG: [i32; 6] = [0, 4, 5, 2, 1, 0];
+const MOBILITY_EG: [i32; 6] = [0, 4, 5, 4, 2, 0];
+// About how many squares a piece reaches in a normal position. Having more is a bonus and less
+// a penalty, so mobility doesn't add up to a big number in every position.
+const BASELINE: [i32; 6] = [0, 4, 6, 6, 12, 0];
+
+/// Mobility of the position, positive when white's pieces are freer
+pub fn evaluate_mobility(board: &Board) -> TaperedScore {
+    mobility(board, Color::White) - mobility(board, Color::Black)
+}
+
+fn mobility(board: &Board, color: Color) -> TaperedScore {
+    let occupied = *board.combined();
+    let available = !board.color_combined(color) & !pawn_attacks(board, !color);
+    let mut score = TaperedScore::default();
+
+    for piece in [Piece::Knight, Piece::Bishop, Piece::Rook, Piece::Queen] {
+        let index = piece.to_index();
+
+        for square in board.pieces(piece) & board.color_combined(color) {
+            let attacks = match piece {
+                Piece::Knight => get_knight_moves(square),
+                Piece::Bishop => get_bishop_moves(square, occupied),
+                Piece::Rook => get_rook_moves(square, occupied),
+                _ => get_bishop_moves(square, occupied) | get_rook_moves(square, occupied),
+            };
+            let count = (attacks & available).popcnt() as i32 - BASELINE[index];
+
+            score += TaperedScore::new(MOBILITY_MG[index], MOBILITY_EG[index]) * count;
+        }
+    }
+
+    score
+}
+
+/// Every square a pawn of `color` attacks
+pub fn pawn_attacks(board: &Board, color: Color) -> BitBoard {
+    let mut attacks = EMPTY;
+
+    for square in board.pieces(Piece::Pawn) & board.color_combined(color) {
+        attacks |= get_pawn_attacks(square, color, !EMPTY);
+    }
+
+    attacks
+}
@@ -0,0 +1,169 @@
+//! Pawn structure: doubled, isolated, backward and passed pawns. Pawns move rarely and the same
+//! structure shows up in a huge part of the tree, so everything that only depends on where the
+//! pawns stand is cached in a pawn hash table. Passed pawns are remembered in there as well, but
+//! their bonus also depends on whether other pieces are in the way, so it's computed every time.
+
+use chess::{
+    get_adjacent_files, get_file, get_pawn_attacks, BitBoard, Board, Color, Piece, Square, EMPTY,
+};
+
+use super::TaperedScore;
+
+/// Number of entries in a pawn table, each thread has its own
+pub const PAWN_TABLE_SIZE: usize = 16384;
+
+const DOUBLED: TaperedScore = TaperedScore::new(-10, -20);
+const ISOLATED: TaperedScore = TaperedScore::new(-10, -15);
+const BACKWARD: TaperedScore = TaperedScore::new(-8, -10);
+// Indexed by rank from the pawn's own side, a pawn on its 7th rank is one step from promoting
+const PASSED_MG: [i32; 8] = [0, 5, 10, 15, 25, 40, 60, 0];
+const PASSED_EG: [i32; 8] = [0, 10, 15, 25, 45, 70, 110, 0];
+
+#[derive(Clone, Copy)]
+struct PawnEntry {
+    white_pawns: BitBoard,
+    black_pawns: BitBoard,
+    /// Doubled, isolated and backward pawns, white minus black
+    structure: TaperedScore,
+    /// Passed pawns of white and black
+    passed: [BitBoard; 2],
+}
+
+/// Cache of pawn structure evaluations, indexed by the pawn hash and verified against the pawn
+/// bitboards themselves so a collision can never return the wrong structure
+pub struct PawnTable {
+    entries: Vec<Option<PawnEntry>>,
+}
+
+impl PawnTable {
+    pub fn new() -> Self {
+        PawnTable {
+            entries: vec![None; PAWN_TABLE_SIZE],
+        }
+    }
+
+    fn probe(&mut self, board: &Board) -> PawnEntry {
+        let white_pawns = board.pieces(Piece::Pawn) & board.color_combined(Color::White);
+        let black_pawns = board.pieces(Piece::Pawn) & board.color_combined(Color::Black);
+        let index = (board.get_pawn_hash() % self.entries.len() as u64) as usize;
+
+        match self.entries[index] {
+            Some(entry) if entry.white_pawns == white_pawns && entry.black_pawns == black_pawns => {
+                entry
+            }
+            _ => {
+                let entry = PawnEntry {
+                    white_pawns,
+                    black_pawns,
+                    structure: structure(white_pawns, black_pawns, Color::White)
+                        - structure(black_pawns, white_pawns, Color::Black),
+                    passed: [
+                        passed_pawns(white_pawns, black_pawns, Color::White),
+                        passed_pawns(black_pawns, white_pawns, Color::Black),
+                    ],
+                };
+                self.entries[index] = Some(entry);
+                entry
+            }
+        }
+    }
+}
+
+/// Pawn structure score of the position, positive when white's pawns are better
+pub fn evaluate_pawns(board: &Board, table: &mut PawnTable) -> TaperedScore {
+    let entry = table.probe(board);
+
+    entry.structure + passed_bonus(board, entry.passed[0], Color::White)
+        - passed_bonus(board, entry.passed[1], Color::Black)
+}
+
+fn structure(own_pawns: BitBoard, enemy_pawns: BitBoard, color: Color) -> TaperedScore {
+    let mut score = TaperedScore::default();
+
+    for square in own_pawns {
+        let file = get_file(square.get_file());
+        let adjacent = get_adjacent_files(square.get_file());
+
+        // Only the rear pawn of a doubled pair is penalized, the front one may still be passed
+        if own_pawns & file & ranks_ahead(square, color) != EMPTY {
+            score += DOUBLED;
+        }
+
+        if own_pawns & adjacent == EMPTY {
+            score += ISOLATED;
+        } else if is_backward(square, own_pawns, enemy_pawns, color) {
+            score += BACKWARD;
+        }
+    }
+
+    score
+}
+
+// A pawn whose neighbours have all advanced past it, and that can't step up to them because an
+// enemy pawn controls the square in front
+fn is_backward(square: Square, own_pawns: BitBoard, enemy_pawns: BitBoard, color: Color) -> bool {
+    let supporters =
+        own_pawns & get_adjacent_files(square.get_file()) & !ranks_ahead(square, color);
+    if supporters != EMPTY {
+        return false;
+    }
+
+    match square.forward(color) {
+        Some(stop) => get_pawn_attacks(stop, color, enemy_pawns) != EMPTY,
+        None => false,
+    }
+}
+
+fn passed_pawns(own_pawns: BitBoard, enemy_pawns: BitBoard, color: Color) -> BitBoard {
+    let mut passed = EMPTY;
+
+    for square in own_pawns {
+        let ahead = ranks_ahead(square, color);
+        let file = get_file(square.get_file());
+        let front_span = (file | get_adjacent_files(square.get_file())) & ahead;
+
+        if enemy_pawns & front_span == EMPTY && own_pawns & file & ahead == EMPTY {
+            passed |= BitBoard::from_square(square);
+        }
+    }
+
+    passed
+}
+
+// Passed pawns get more valuable the closer they are to promoting. Anything standing on the way
+// to the promotion square has to be removed first, which halves the bonus.
+fn passed_bonus(board: &Board, passed: BitBoard, color: Color) -> TaperedScore {
+    let mut score = TaperedScore::default();
+
+    for square in passed {
+        let rank = relative_rank(square, color);
+        let path = get_file(square.get_file()) & ranks_ahead(square, color);
+        let bonus = TaperedScore::new(PASSED_MG[rank], PASSED_EG[rank]);
+
+        if board.combined() & path != EMPTY {
+            score += TaperedScore::new(bonus.mg / 2, bonus.eg / 2);
+        } else {
+            score += bonus;
+        }
+    }
+
+    score
+}
+
+/// All squares on ranks in front of `square`, seen from `color`
+pub fn ranks_ahead(square: Square, color: Color) -> BitBoard {
+    let rank = square.get_rank().to_index() as u32;
+
+    match color {
+        Color::White => BitBoard(u64::MAX.checked_shl(8 * (rank + 1)).unwrap_or(0)),
+        Color::Black => BitBoard((1u64 << (8 * rank)) - 1),
+    }
+}
+
+/// Rank of the square from `color`'s own side of the board, 0 is its back rank
+pub fn relative_rank(square: Square, color: Color) -> usize {
+    match color {
+        Color::White => square.get_rank().to_index(),
+        Color::Black => 7 - square.get_rank().to_index(),
+    }
+}
@@ -0,0 +1,160 @@
+//! Decides in which order the moves of a node are searched. Alpha-beta cuts off the most when the
+//! best move is searched first, so the order is: the best move the transposition table remembers,
+//! captures that don't lose material from the most valuable victim and least valuable attacker
+//! (MVV-LVA), the killer moves that caused a cutoff at the same ply elsewhere in the tree, the
+//! other quiet moves by how often they caused cutoffs before (history heuristic), and last the
+//! captures that lose material according to the static exchange evaluation.
+
+use chess::{Board, ChessMove, MoveGen, Piece};
+
+use super::evaluate::PIECE_VALUES;
+use super::see;
+
+/// Deepest ply killer moves are kept for
+pub const MAX_PLY: usize = 128;
+
+const HASH_MOVE_SCORE: i32 = 1_000_000;
+const CAPTURE_SCORE: i32 = 100_000;
+const BAD_CAPTURE_SCORE: i32 = -100_000;
+const FIRST_KILLER_SCORE: i32 = 90_000;
+const SECOND_KILLER_SCORE: i32 = 89_000;
+// History scores are halved once one of them gets this big, so they stay below the killers
+const HISTORY_MAX: i32 = 80_000;
+
+/// Killer and history tables of one search thread
+pub struct MoveOrdering {
+    killers: [[Option<ChessMove>; 2]; MAX_PLY],
+    // Indexed by side to move, source square and destination square
+    history: [[[i32; 64]; 64]; 2],
+}
+
+impl MoveOrdering {
+    pub fn new() -> Self {
+        MoveOrdering {
+            killers: [[None; 2]; MAX_PLY],
+            history: [[[0; 64]; 64]; 2],
+        }
+    }
+
+    /// Gets the tables ready for the next move of the game. Killers belong to plies of the last
+    /// search, which don't line up with the new one, but the history stays useful, just with less
+    /// weight.
+    pub fn new_search(&mut self) {
+        self.killers = [[None; 2]; MAX_PLY];
+
+        for color in self.history.iter_mut() {
+            for from in color.iter_mut() {
+                for score in from.iter_mut() {
+                    *score /= 2;
+                }
+            }
+        }
+    }
+
+    /// Returns the legal moves of the position sorted from most to least promising
+    pub fn order_moves(
+        &self,
+        board: &Board,
+        hash_move: Option<ChessMove>,
+        ply: usize,
+    ) -> Vec<ChessMove> {
+        let mut scored_moves: Vec<(ChessMove, i32)> = MoveGen::new_legal(board)
+            .map(|chess_move| {
+                (
+                    chess_move,
+                    self.score_move(board, chess_move, hash_move, ply),
+                )
+            })
+            .collect();
+
+        scored_moves.sort_by_key(|(_, score)| -score);
+
+        scored_moves
+            .into_iter()
+            .map(|(chess_move, _)| chess_move)
+            .collect()
+    }
+
+    fn score_move(
+        &self,
+        board: &Board,
+        chess_move: ChessMove,
+        hash_move: Option<ChessMove>,
+        ply: usize,
+    ) -> i32 {
+        if Some(chess_move) == hash_move {
+            HASH_MOVE_SCORE
+        } else if is_tactical(board, chess_move) {
+            if see::see_ge(board, chess_move, 0) {
+                CAPTURE_SCORE + mvv_lva(board, chess_move)
+            } else {
+                BAD_CAPTURE_SCORE + mvv_lva(board, chess_move)
+            }
+        } else if ply < MAX_PLY && self.killers[ply][0] == Some(chess_move) {
+            FIRST_KILLER_SCORE
+        } else if ply < MAX_PLY && self.killers[ply][1] == Some(chess_move) {
+            SECOND_KILLER_SCORE
+        } else {
+            self.history[board.side_to_move().to_index()][chess_move.get_source().to_index()]
+                [chess_move.get_dest().to_index()]
+        }
+    }
+
+    pub fn is_killer(&self, chess_move: ChessMove, ply: usize) -> bool {
+        ply < MAX_PLY && self.killers[ply].contains(&Some(chess_move))
+    }
+
+    /// Remembers a quiet move that caused a beta cutoff, both as a killer for its ply and in the
+    /// history table. Deeper cutoffs count for more, since they saved more work.
+    pub fn record_cutoff(&mut self, board: &Board, chess_move: ChessMove, depth: i32, ply: usize) {
+        if ply < MAX_PLY && self.killers[ply][0] != Some(chess_move) {
+            self.killers[ply][1] = self.killers[ply][0];
+            self.killers[ply][0] = Some(chess_move);
+        }
+
+        let color = board.side_to_move().to_index();
+        let entry = &mut self.history[color][chess_move.get_source().to_index()]
+            [chess_move.get_dest().to_index()];
+        *entry += depth * depth;
+
+        if *entry > HISTORY_MAX {
+            for from in self.history[color].iter_mut() {
+                for score in from.iter_mut() {
+                    *score /= 2;
+                }
+            }
+        }
+    }
+}
+
+/// Captures and promotions, which change the material balance
+pub fn is_tactical(board: &Board, chess_move: ChessMove) -> bool {
+    chess_move.get_promotion().is_some() || captured_piece(board, chess_move).is_some()
+}
+
+/// The piece a move captures. En passant takes a pawn that isn't on the destination square.
+pub fn captured_piece(board: &Board, chess_move: ChessMove) -> Option<Piece> {
+    match board.piece_on(chess_move.get_dest()) {
+        Some(piece) => Some(piece),
+        None if board.piece_on(chess_move.get_source()) == Some(Piece::Pawn)
+            && chess_move.get_source().get_file() != chess_move.get_dest().get_file() =>
+        {
+            Some(Piece::Pawn)
+        }
+        None => None,
+    }
+}
+
+/// Most valuable victim first, and among those the least valuable attacker first
+pub fn mvv_lva(board: &Board, chess_move: ChessMove) -> i32 {
+    let victim =
+        captured_piece(board, chess_move).map_or(0, |piece| PIECE_VALUES[piece.to_index()]);
+    let promotion = chess_move
+        .get_promotion()
+        .map_or(0, |piece| PIECE_VALUES[piece.to_index()]);
+    let attacker = board
+        .piece_on(chess_move.get_source())
+        .map_or(0, |piece| piece.to_index() as i32);
+
+    (victim + promotion) * 10 - attacker
+}
@@ -0,0 +1,112 @@
+//! Scores in integer centipawns, always from the point of view of the side to move. Mates are
+//! encoded as values close to `MATE`: a mate delivered `n` plies from the root scores
+//! `MATE - n`, so shorter mates are preferred and getting mated later is preferred over sooner.
+//! The transposition table can't store distances from the root, since the same position is
+//! reached at different plies, so mate scores are converted to distances from the node itself
+//! when stored and back when read.
+
+use std::fmt;
+use std::ops::{Add, Neg, Sub};
+
+use super::move_ordering::MAX_PLY;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct Score(i32);
+
+impl Score {
+    pub const DRAW: Score = Score(0);
+    pub const MATE: Score = Score(32_000);
+    /// Bigger than any real score, used as the initial bounds of the search window
+    pub const INFINITY: Score = Score(32_001);
+    // Scores at least this far from zero are mates
+    const MATE_BOUND: i32 = 32_000 - MAX_PLY as i32;
+
+    pub const fn cp(centipawns: i32) -> Score {
+        Score(centipawns)
+    }
+
+    pub fn centipawns(self) -> i32 {
+        self.0
+    }
+
+    /// The side to move mates in `ply` plies
+    pub fn mate_in(ply: i32) -> Score {
+        Score(Score::MATE.0 - ply)
+    }
+
+    /// The side to move gets mated in `ply` plies
+    pub fn mated_in(ply: i32) -> Score {
+        Score(-Score::MATE.0 + ply)
+    }
+
+    pub fn is_mate(self) -> bool {
+        self.0.abs() >= Score::MATE_BOUND
+    }
+
+    /// Moves until mate like UCI reports it, negative when the side to move gets mated
+    pub fn mate_moves(self) -> Option<i32> {
+        if !self.is_mate() {
+            None
+        } else if self.0 > 0 {
+            Some((Score::MATE.0 - self.0 + 1) / 2)
+        } else {
+            Some(-(Score::MATE.0 + self.0) / 2)
+        }
+    }
+
+    /// Converts a mate score from distance to the root into distance to the node at `ply`
+    pub fn to_tt(self, ply: i32) -> Score {
+        if self.0 >= Score::MATE_BOUND {
+            Score(self.0 + ply)
+        } else if self.0 <= -Score::MATE_BOUND {
+            Score(self.0 - ply)
+        } else {
+            self
+        }
+    }
+
+    /// Converts a mate score read from the transposition table back into distance to the root
+    pub fn to_search(self, ply: i32) -> Score {
+        if self.0 >= Score::MATE_BOUND {
+            Score(self.0 - ply)
+        } else if self.0 <= -Score::MATE_BOUND {
+            Score(self.0 + ply)
+        } else {
+            self
+        }
+    }
+}
+
+impl Neg for Score {
+    type Output = Score;
+
+    fn neg(self) -> Score {
+        Score(-self.0)
+    }
+}
+
+impl Add for Score {
+    type Output = Score;
+
+    fn add(self, other: Score) -> Score {
+        Score(self.0 + other.0)
+    }
+}
+
+impl Sub for Score {
+    type Output = Score;
+
+    fn sub(self, other: Score) -> Score {
+        Score(self.0 - other.0)
+    }
+}
+
+/// Formats the score the way UCI `info score` expects it
+impl fmt::Display for Score {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.mate_moves() {
+            Some(moves) => write!(f, "mate {}", moves),
+            None => write!(f, "cp {}", self.0),
+        }
+    }
+}
@@ -0,0 +1,140 @@
+//! Static exchange evaluation (SEE). Plays out all captures on the destination square of a move,
+//! each side always taking with its least valuable piece, and either side may stop capturing when
+//! going on would lose material. The result is how much material the move wins or loses, without
+//! searching anything. Sliders behind the pieces that already captured join in (x-rays), and pawns
+//! that capture onto the last rank become queens.
+//! Pins are ignored, so a pinned piece is assumed to capture like any other.
+
+use chess::{
+    get_bishop_moves, get_king_moves, get_knight_moves, get_pawn_attacks, get_rook_moves, BitBoard,
+    Board, ChessMove, Color, Piece, Square, EMPTY,
+};
+
+use super::evaluate::PIECE_VALUES;
+use super::move_ordering;
+
+// The king can only capture last, so its value never ends up in the result
+const KING_VALUE: i32 = 20_000;
+// Least valuable attacker first
+const ATTACKER_ORDER: [Piece; 6] = [
+    Piece::Pawn,
+    Piece::Knight,
+    Piece::Bishop,
+    Piece::Rook,
+    Piece::Queen,
+    Piece::King,
+];
+
+/// Material the side to move wins with `chess_move` in centipawns once the exchange on its
+/// destination square is over, negative when the move loses material
+pub fn see(board: &Board, chess_move: ChessMove) -> i32 {
+    let source = chess_move.get_source();
+    let dest = chess_move.get_dest();
+    let Some(moving_piece) = board.piece_on(source) else {
+        return 0;
+    };
+
+    let mut occupied = *board.combined() ^ BitBoard::from_square(source);
+    if moving_piece == Piece::Pawn && source.get_file() != dest.get_file() {
+        if let Some(en_passant) = board
+            .en_passant()
+            .filter(|_| board.piece_on(dest).is_none())
+        {
+            // The pawn taken en passant isn't on the destination square
+            occupied ^= BitBoard::from_square(en_passant);
+        }
+    }
+
+    // gains[n] is what the side that makes the n-th capture has won, if the exchange stops there
+    let mut gains = vec![move_ordering::captured_piece(board, chess_move).map_or(0, value)];
+    let mut on_square = match chess_move.get_promotion() {
+        Some(promotion) => {
+            gains[0] += value(promotion) - value(Piece::Pawn);
+            promotion
+        }
+        None => moving_piece,
+    };
+    let mut side = !board.side_to_move();
+
+    loop {
+        let attackers = attackers_to(board, dest, occupied) & occupied;
+        let Some((square, piece)) = least_valuable_attacker(board, attackers, side) else {
+            break;
+        };
+
+        // The king can't take when the square is still defended
+        if piece == Piece::King && (attackers & board.color_combined(!side)) != EMPTY {
+            break;
+        }
+
+        let mut gain = value(on_square) - gains[gains.len() - 1];
+        on_square = piece;
+        if piece == Piece::Pawn && promotes_on(dest, side) {
+            gain += value(Piece::Queen) - value(Piece::Pawn);
+            on_square = Piece::Queen;
+        }
+
+        gains.push(gain);
+        occupied ^= BitBoard::from_square(square);
+        side = !side;
+    }
+
+    // Going backwards, every side picks between the capture and not starting it at all
+    while gains.len() > 1 {
+        let last = gains.pop().expect("At least two gains");
+        let previous = gains.last_mut().expect("At least one gain");
+        *previous = -(-*previous).max(last);
+    }
+
+    gains[0]
+}
+
+/// Whether `chess_move` wins at least `threshold` centipawns of material. With a threshold of 0
+/// that tells captures that don't lose anything from bad ones.
+pub fn see_ge(board: &Board, chess_move: ChessMove, threshold: i32) -> bool {
+    see(board, chess_move) >= threshold
+}
+
+fn value(piece: Piece) -> i32 {
+    match piece {
+        Piece::King => KING_VALUE,
+        _ => PIECE_VALUES[piece.to_index()],
+    }
+}
+
+fn promotes_on(square: Square, color: Color) -> bool {
+    square.get_rank() == color.to_their_backrank()
+}
+
+/// Pieces of both colors that attack `square`. Sliders are blocked by `occupied` only, so the
+/// ones behind pieces that already left it show up.
+fn attackers_to(board: &Board, square: Square, occupied: BitBoard) -> BitBoard {
+    let pawns = board.pieces(Piece::Pawn);
+    let diagonal = board.pieces(Piece::Bishop) | board.pieces(Piece::Queen);
+    let straight = board.pieces(Piece::Rook) | board.pieces(Piece::Queen);
+
+    // A pawn attacks the square if a pawn of the other color on it would attack the pawn
+    (get_pawn_attacks(square, Color::Black, !EMPTY) & pawns & board.color_combined(Color::White))
+        | (get_pawn_attacks(square, Color::White, !EMPTY)
+            & pawns
+            & board.color_combined(Color::Black))
+        | (get_knight_moves(square) & board.pieces(Piece::Knight))
+        | (get_bishop_moves(square, occupied) & diagonal)
+        | (get_rook_moves(square, occupied) & straight)
+        | (get_king_moves(square) & board.pieces(Piece::King))
+}
+
+fn least_valuable_attacker(
+    board: &Board,
+    attackers: BitBoard,
+    color: Color,
+) -> Option<(Square, Piece)> {
+    let attackers = attackers & board.color_combined(color);
+
+    ATTACKER_ORDER.iter().find_map(|piece| {
+        (attackers & board.pieces(*piece))
+            .into_iter()
+            .next()
+            .map(|square| (square, *piece))
+    })
+}
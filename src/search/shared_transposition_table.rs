@@ -0,0 +1,245 @@
+//! Transposition table that all search threads probe and store into at the same time, without a
+//! lock. Entries are packed into a single u64, and each slot keeps two atomics: the packed entry
+//! and the key xored with it. A thread that reads a slot while another one writes it sees a key
+//! that doesn't match and treats it as a miss, so torn entries are never used.
+//! Buckets and replacement work the same as in `TransTable`.
+
+use std::mem;
+use std::sync::atomic::{AtomicU64, AtomicU8, Ordering};
+
+use chess::{Board, ChessMove, Piece, ALL_SQUARES};
+
+use super::score::Score;
+use super::transposition_table::{self, Flag, TransTableEntry, DEFAULT_SIZE_MB};
+
+// Layout of a packed entry, from the lowest bit up
+const EVAL_SHIFT: u64 = 0;
+const MOVE_SHIFT: u64 = 32;
+const DEPTH_SHIFT: u64 = 48;
+const FLAG_SHIFT: u64 = 56;
+const GENERATION_SHIFT: u64 = 58;
+const GENERATION_MASK: u8 = 0x3f;
+
+#[derive(Default)]
+struct Slot {
+    key_xor_data: AtomicU64,
+    data: AtomicU64,
+}
+
+impl Slot {
+    /// Returns the packed entry if it belongs to `key` and wasn't torn by a concurrent write
+    fn load(&self, key: u64) -> Option<u64> {
+        let data = self.data.load(Ordering::Relaxed);
+        let key_xor_data = self.key_xor_data.load(Ordering::Relaxed);
+
+        if key_xor_data ^ data == key && key_xor_data != 0 {
+            Some(data)
+        } else {
+            None
+        }
+    }
+
+    /// Returns the key and packed entry of whatever the slot holds
+    fn read(&self) -> Option<(u64, u64)> {
+        let data = self.data.load(Ordering::Relaxed);
+        let key_xor_data = self.key_xor_data.load(Ordering::Relaxed);
+
+        if key_xor_data == 0 {
+            None
+        } else {
+            Some((key_xor_data ^ data, data))
+        }
+    }
+
+    fn store(&self, key: u64, data: u64) {
+        self.data.store(data, Ordering::Relaxed);
+        self.key_xor_data.store(key ^ data, Ordering::Relaxed);
+    }
+
+    fn clear(&self) {
+        self.data.store(0, Ordering::Relaxed);
+        self.key_xor_data.store(0, Ordering::Relaxed);
+    }
+}
+
+#[derive(Default)]
+struct Bucket {
+    depth_preferred: Slot,
+    always_replace: Slot,
+}
+
+pub struct SharedTransTable {
+    buckets: Vec<Bucket>,
+    // Bumped for every search, entries from older searches are replaced first
+    generation: AtomicU8,
+}
+
+#[allow(dead_code)]
+impl SharedTransTable {
+    pub fn new() -> Self {
+        SharedTransTable::with_size_mb(DEFAULT_SIZE_MB)
+    }
+
+    pub fn with_size_mb(size_mb: usize) -> Self {
+        let bucket_count = (size_mb * 1024 * 1024 / mem::size_of::<Bucket>()).max(1);
+
+        SharedTransTable {
+            buckets: (0..bucket_count).map(|_| Bucket::default()).collect(),
+            generation: AtomicU8::new(0),
+        }
+    }
+
+    /// Empties the table. Must not run while a search is using it, or stale entries may survive.
+    pub fn clear(&self) {
+        for bucket in &self.buckets {
+            bucket.depth_preferred.clear();
+            bucket.always_replace.clear();
+        }
+        self.generation.store(0, Ordering::Relaxed);
+    }
+
+    /// Marks the start of a new search, so entries of earlier ones age
+    pub fn new_search(&self) {
+        let generation = self.generation.load(Ordering::Relaxed);
+        self.generation.store(
+            generation.wrapping_add(1) & GENERATION_MASK,
+            Ordering::Relaxed,
+        );
+    }
+
+    pub fn get(&self, hash: u64) -> Option<TransTableEntry> {
+        let bucket = &self.buckets[self.index(hash)];
+
+        bucket
+            .depth_preferred
+            .load(hash)
+            .or_else(|| bucket.always_replace.load(hash))
+            .map(|data| unpack(data).0)
+    }
+
+    pub fn add_entry(&self, board: Board, entry: TransTableEntry) {
+        let key = board.get_hash();
+        let generation = self.generation.load(Ordering::Relaxed);
+        let bucket = &self.buckets[self.index(key)];
+        let data = pack(&entry, generation);
+
+        // The deep slot only gives way to entries that are at least as deep, unless what it
+        // holds is from an older search or about the same position
+        let old = bucket.depth_preferred.read();
+        let replace_deep = match old {
+            Some((old_key, old_data)) => {
+                let (old_entry, old_generation) = unpack(old_data);
+                old_key == key || old_generation != generation || entry.depth >= old_entry.depth
+            }
+            None => true,
+        };
+
+        if replace_deep {
+            // The demoted entry still has a chance to be useful, unless it's the same position.
+            // A torn read here only moves a garbage entry no real position will ever match.
+            if let Some((old_key, old_data)) = old {
+                if old_key != key {
+                    bucket.always_replace.store(old_key, old_data);
+                }
+            }
+            bucket.depth_preferred.store(key, data);
+        } else {
+            bucket.always_replace.store(key, data);
+        }
+    }
+
+    /// How full the table is in permille, from a sample of the first thousand buckets. Only
+    /// entries of the current search count, like UCI asks for.
+    pub fn hashfull(&self) -> usize {
+        let generation = self.generation.load(Ordering::Relaxed);
+        transposition_table::hashfull(&self.buckets, |bucket| {
+            [&bucket.depth_preferred, &bucket.always_replace]
+                .into_iter()
+                .filter_map(Slot::read)
+                .filter(|(_, data)| unpack(*data).1 == generation)
+                .count()
+        })
+    }
+
+    /// Follows the best moves stored in the table from `board` onwards, see
+    /// `transposition_table::principal_variation`
+    pub fn principal_variation(&self, board: Board, max_length: usize) -> Vec<ChessMove> {
+        transposition_table::principal_variation(board, max_length, |hash| {
+            self.get(hash).and_then(|entry| entry.best_move)
+        })
+    }
+
+    fn index(&self, hash: u64) -> usize {
+        (hash % self.buckets.len() as u64) as usize
+    }
+}
+
+fn pack(entry: &TransTableEntry, generation: u8) -> u64 {
+    let flag: u64 = match entry.flag {
+        Flag::Lowerbound => 0,
+        Flag::Upperbound => 1,
+        Flag::Exact => 2,
+        Flag::None => 3,
+    };
+
+    (u64::from(entry.eval.centipawns() as i16 as u16) << EVAL_SHIFT)
+        | (u64::from(pack_move(entry.best_move)) << MOVE_SHIFT)
+        | (u64::from(entry.depth.clamp(0, 255) as u8) << DEPTH_SHIFT)
+        | (flag << FLAG_SHIFT)
+        | (u64::from(generation & GENERATION_MASK) << GENERATION_SHIFT)
+}
+
+fn unpack(data: u64) -> (TransTableEntry, u8) {
+    let flag = match (data >> FLAG_SHIFT) & 0b11 {
+        0 => Flag::Lowerbound,
+        1 => Flag::Upperbound,
+        2 => Flag::Exact,
+        _ => Flag::None,
+    };
+
+    let entry = TransTableEntry {
+        flag,
+        eval: Score::cp(i32::from((data >> EVAL_SHIFT) as u16 as i16)),
+        depth: i32::from((data >> DEPTH_SHIFT) as u8),
+        best_move: unpack_move((data >> MOVE_SHIFT) as u16),
+    };
+
+    (entry, (data >> GENERATION_SHIFT) as u8 & GENERATION_MASK)
+}
+
+// Source and destination take 6 bits each, the promotion 3. Zero is no move, since a move from
+// a1 to a1 can't exist.
+fn pack_move(chess_move: Option<ChessMove>) -> u16 {
+    match chess_move {
+        Some(chess_move) => {
+            let promotion = match chess_move.get_promotion() {
+                None => 0,
+                Some(piece) => piece.to_index() as u16,
+            };
+
+            chess_move.get_source().to_index() as u16
+                | (chess_move.get_dest().to_index() as u16) << 6
+                | promotion << 12
+        }
+        None => 0,
+    }
+}
+
+fn unpack_move(packed: u16) -> Option<ChessMove> {
+    if packed == 0 {
+        return None;
+    }
+
+    let promotion = match packed >> 12 {
+        1 => Some(Piece::Knight),
+        2 => Some(Piece::Bishop),
+        3 => Some(Piece::Rook),
+        4 => Some(Piece::Queen),
+        _ => None,
+    };
+
+    let source = ALL_SQUARES[(packed & 0x3f) as usize];
+    let dest = ALL_SQUARES[((packed >> 6) & 0x3f) as usize];
+
+    Some(ChessMove::new(source, dest, promotion))
+}
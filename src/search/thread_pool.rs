@@ -0,0 +1,306 @@
+//! Search threads that live as long as the engine that owns them. The workers park between
+//! searches, all pick up the same job when one comes in, and report what they found when they
+//! stop. The main worker decides when the search is over, the last worker to report picks the
+//! move from all results. Every worker keeps its search context, so the history tables it learned
+//! carry over to the next move. A worker that panics still reports, and the panic is passed on to
+//! whoever waits for the search.
+
+use std::any::Any;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::AtomicBool;
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::thread::{self, JoinHandle};
+
+use chess::Board;
+use log::debug;
+
+use super::draw::GameHistory;
+use super::shared_transposition_table::SharedTransTable;
+use super::time_management::{SearchLimits, TimeManager};
+use super::{SearchContext, SearchOptions, SearchResult, ThreadResult, MAX_DEPTH};
+
+/// Called with the result once all workers reported, before anyone waiting is woken up
+pub type OnFinish = Box<dyn FnOnce(&SearchResult) + Send>;
+
+/// A search for all workers to run
+pub struct SearchJob {
+    pub board: Board,
+    pub options: SearchOptions,
+    pub tt: Arc<SharedTransTable>,
+    pub stop_now: Arc<AtomicBool>,
+    pub history: GameHistory,
+    pub timer: TimeManager,
+    pub target_depth: i32,
+    /// `go infinite`, the result is held back until the search is stopped
+    pub infinite: bool,
+}
+
+impl SearchJob {
+    /// The clock of the search starts running here
+    pub fn new(
+        board: Board,
+        limits: &SearchLimits,
+        options: SearchOptions,
+        tt: Arc<SharedTransTable>,
+        stop_now: Arc<AtomicBool>,
+        history: GameHistory,
+    ) -> Self {
+        SearchJob {
+            board,
+            options,
+            tt,
+            stop_now,
+            history,
+            timer: TimeManager::new(limits, board.side_to_move()),
+            // `go depth 0` still needs a move, so at least one ply is searched
+            target_depth: limits.depth.unwrap_or(MAX_DEPTH).clamp(1, MAX_DEPTH),
+            infinite: limits.infinite,
+        }
+    }
+}
+
+#[derive(Default)]
+struct PoolState {
+    job: Option<Arc<SearchJob>>,
+    // Bumped for every job, so no worker runs the same one twice
+    generation: u64,
+    // Bumped for every new game, the workers throw away what they learned when it changes
+    game: u64,
+    // Workers that haven't reported on the current job yet
+    running: usize,
+    results: Vec<ThreadResult>,
+    nodes: u64,
+    on_finish: Option<OnFinish>,
+    result: Option<SearchResult>,
+    // The first panic of a worker on the current job, for the next one who waits
+    panic: Option<Box<dyn Any + Send>>,
+    quit: bool,
+}
+
+#[derive(Default)]
+struct Shared {
+    state: Mutex<PoolState>,
+    // Wakes the workers for a new job or to quit
+    start: Condvar,
+    // Wakes whoever waits for the workers to report
+    done: Condvar,
+}
+
+impl Shared {
+    fn lock(&self) -> MutexGuard<'_, PoolState> {
+        self.state.lock().expect("Search thread panicked")
+    }
+}
+
+pub struct ThreadPool {
+    shared: Arc<Shared>,
+    workers: Vec<JoinHandle<()>>,
+}
+
+impl ThreadPool {
+    /// Starts `threads` parked workers, at least one
+    pub fn new(threads: usize) -> Self {
+        let shared = Arc::new(Shared::default());
+        let workers = (0..threads.max(1))
+            .map(|index| {
+                let shared = shared.clone();
+                thread::spawn(move || worker(index, &shared))
+            })
+            .collect();
+
+        ThreadPool { shared, workers }
+    }
+
+    pub fn threads(&self) -> usize {
+        self.workers.len()
+    }
+
+    /// Hands a job to all workers and returns right away. A search that is still running is
+    /// waited for first.
+    pub fn start(&self, job: SearchJob, on_finish: Option<OnFinish>) {
+        let mut state = self.wait_idle();
+
+        job.tt.new_search();
+        debug!(
+            "Time budget: soft {:?}, hard {:?}",
+            job.timer.soft_limit(),
+            job.timer.hard_limit()
+        );
+
+        state.job = Some(Arc::new(job));
+        state.generation += 1;
+        state.running = self.workers.len();
+        state.results.clear();
+        state.nodes = 0;
+        state.on_finish = on_finish;
+        state.result = None;
+        self.shared.start.notify_all();
+    }
+
+    /// Makes `result` the result of the last search, for a search that didn't need the workers
+    pub fn report(&self, result: SearchResult) {
+        self.wait_idle().result = Some(result);
+    }
+
+    /// Waits until all workers reported and returns the result of the last search, if there
+    /// was one. Panics if a worker panicked during the search.
+    pub fn wait(&self) -> Option<SearchResult> {
+        self.wait_idle().result.clone()
+    }
+
+    /// Runs a job to the end
+    pub fn search(&self, job: SearchJob) -> SearchResult {
+        self.start(job, None);
+        self.wait().expect("A finished search has a result")
+    }
+
+    /// Makes the workers forget what they learned, for when a new game starts. Takes effect with
+    /// the next job.
+    pub fn new_game(&self) {
+        self.wait_idle().game += 1;
+    }
+
+    fn wait_idle(&self) -> MutexGuard<'_, PoolState> {
+        let mut state = self.shared.lock();
+        while state.running > 0 {
+            state = self
+                .shared
+                .done
+                .wait(state)
+                .expect("Search thread panicked");
+        }
+
+        if let Some(payload) = state.panic.take() {
+            drop(state);
+            panic::resume_unwind(payload);
+        }
+        state
+    }
+}
+
+impl Drop for ThreadPool {
+    fn drop(&mut self) {
+        {
+            let mut state = self.shared.lock();
+            if let Some(job) = &state.job {
+                job.stop_now
+                    .store(true, std::sync::atomic::Ordering::Release);
+            }
+            state.quit = true;
+            self.shared.start.notify_all();
+        }
+
+        for worker in self.workers.drain(..) {
+            worker.join().expect("Search thread panicked");
+        }
+    }
+}
+
+fn worker(index: usize, shared: &Shared) {
+    let mut context: Option<SearchContext> = None;
+    let mut generation = 0;
+    let mut game = 0;
+
+    loop {
+        let job = {
+            let mut state = shared.lock();
+            while !state.quit && state.generation == generation {
+                state = shared.start.wait(state).expect("Search thread panicked");
+            }
+            if state.quit {
+                return;
+            }
+
+            generation = state.generation;
+            if state.game != game {
+                game = state.game;
+                context = None;
+            }
+            state
+                .job
+                .clone()
+                .expect("A new generation comes with a job")
+        };
+
+        // A panic must not keep the others waiting for this worker to report
+        let outcome = panic::catch_unwind(AssertUnwindSafe(|| {
+            let context = match &mut context {
+                Some(context) => {
+                    context.new_search(
+                        job.options.clone(),
+                        job.tt.clone(),
+                        job.stop_now.clone(),
+                        job.timer.clone(),
+                        job.history.clone(),
+                    );
+                    context
+                }
+                None => context.insert(SearchContext::new(
+                    job.options.clone(),
+                    job.tt.clone(),
+                    job.stop_now.clone(),
+                    job.timer.clone(),
+                    job.history.clone(),
+                )),
+            };
+
+            (super::thread_search(index, &job, context), context.nodes)
+        }));
+        if index == 0 || outcome.is_err() {
+            // The helpers only stop on their own at the target depth, and a search that lost a
+            // worker is over
+            job.stop_now
+                .store(true, std::sync::atomic::Ordering::Release);
+        }
+
+        let mut state = shared.lock();
+        match outcome {
+            Ok((result, nodes)) => {
+                state.results.extend(result);
+                state.nodes += nodes;
+            }
+            Err(payload) => {
+                // Whatever the context was in the middle of can't be trusted anymore
+                context = None;
+                state.panic.get_or_insert(payload);
+            }
+        }
+
+        if state.running > 1 {
+            state.running -= 1;
+            continue;
+        }
+
+        // Last one to report. Nobody can start another job before `running` drops to zero, so
+        // the lock isn't needed while the result is put together and reported.
+        let results = std::mem::take(&mut state.results);
+        let on_finish = state.on_finish.take();
+        debug!(
+            "Searched {} nodes in {:?}",
+            state.nodes,
+            job.timer.elapsed()
+        );
+        let panicked = state.panic.is_some();
+        drop(state);
+
+        let outcome = panic::catch_unwind(AssertUnwindSafe(|| {
+            if panicked {
+                return None;
+            }
+
+            let result = super::final_result(&job, &results);
+            if let Some(on_finish) = on_finish {
+                on_finish(&result);
+            }
+            Some(result)
+        }));
+
+        let mut state = shared.lock();
+        match outcome {
+            Ok(result) => state.result = result,
+            Err(payload) => state.panic = Some(payload),
+        }
+        state.running = 0;
+        shared.done.notify_all();
+    }
+}
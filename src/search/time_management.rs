@@ -0,0 +1,165 @@
+//! Decides how long a search may run. The GUI hands over the clock state with `go`, and the
+//! time manager turns it into two limits: a soft one after which no new depth is started, and a
+//! hard one after which the running depth is aborted through `stop_now`.
+//! A search on the opponent's time (pondering) has no limits until the GUI sends `ponderhit`,
+//! then the budget applies from that moment on.
+
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use chess::Color;
+
+// Time kept in reserve for the GUI and OS to pass the move along
+const MOVE_OVERHEAD: Duration = Duration::from_millis(30);
+// Assume this many moves are left if the GUI doesn't tell us
+const DEFAULT_MOVES_TO_GO: u32 = 30;
+
+/// Limits for a single search, mirroring the parameters of the UCI `go` command.
+/// All times are in milliseconds.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SearchLimits {
+    pub wtime: Option<u64>,
+    pub btime: Option<u64>,
+    pub winc: Option<u64>,
+    pub binc: Option<u64>,
+    pub movestogo: Option<u32>,
+    pub movetime: Option<u64>,
+    pub depth: Option<i32>,
+    pub infinite: bool,
+    /// Searching the expected reply of the opponent, the clock only runs after `ponderhit`
+    pub ponder: bool,
+}
+
+impl SearchLimits {
+    /// Search to a fixed depth without a clock
+    pub fn depth(depth: i32) -> Self {
+        SearchLimits {
+            depth: Some(depth),
+            ..SearchLimits::default()
+        }
+    }
+
+    /// Search for a fixed amount of time
+    #[allow(dead_code)]
+    pub fn movetime(milliseconds: u64) -> Self {
+        SearchLimits {
+            movetime: Some(milliseconds),
+            ..SearchLimits::default()
+        }
+    }
+}
+
+// Shared by all copies of a time manager, so a `ponderhit` reaches every search thread
+#[derive(Debug, Default)]
+struct PonderClock {
+    pondering: AtomicBool,
+    // When the ponderhit came, in milliseconds after the search started
+    hit_after: AtomicU64,
+}
+
+#[derive(Clone, Debug)]
+pub struct TimeManager {
+    start: Instant,
+    soft_limit: Option<Duration>,
+    hard_limit: Option<Duration>,
+    ponder: Arc<PonderClock>,
+}
+
+impl TimeManager {
+    /// Allocates the time budget for the side to move. The clock starts when this is called, or
+    /// with `ponderhit` when pondering.
+    pub fn new(limits: &SearchLimits, side_to_move: Color) -> Self {
+        let (soft_limit, hard_limit) = TimeManager::budget(limits, side_to_move);
+
+        TimeManager {
+            start: Instant::now(),
+            soft_limit,
+            hard_limit,
+            ponder: Arc::new(PonderClock {
+                pondering: AtomicBool::new(limits.ponder),
+                hit_after: AtomicU64::new(0),
+            }),
+        }
+    }
+
+    // Soft and hard limit
+    fn budget(limits: &SearchLimits, side_to_move: Color) -> (Option<Duration>, Option<Duration>) {
+        if limits.infinite {
+            return (None, None);
+        }
+
+        if let Some(movetime) = limits.movetime {
+            let budget = Duration::from_millis(movetime).saturating_sub(MOVE_OVERHEAD);
+            return (Some(budget), Some(budget));
+        }
+
+        let (time_left, increment) = if side_to_move == Color::White {
+            (limits.wtime, limits.winc.unwrap_or(0))
+        } else {
+            (limits.btime, limits.binc.unwrap_or(0))
+        };
+
+        match time_left {
+            Some(time_left) => {
+                let time_left = Duration::from_millis(time_left).saturating_sub(MOVE_OVERHEAD);
+                let increment = Duration::from_millis(increment);
+                let moves_to_go = limits.movestogo.unwrap_or(DEFAULT_MOVES_TO_GO).max(1);
+
+                // Spend an even share of the clock plus most of the increment, but allow going
+                // over that when a depth is already running, as long as we never use more than
+                // half of what is left.
+                let soft_limit = (time_left / moves_to_go + increment * 3 / 4).min(time_left / 2);
+                let hard_limit = (soft_limit * 3).min(time_left / 2);
+
+                (Some(soft_limit), Some(hard_limit))
+            }
+            None => (None, None),
+        }
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        self.start.elapsed()
+    }
+
+    /// The opponent played the expected move, so the search is on our own time from now on
+    pub fn ponderhit(&self) {
+        let hit_after = self.elapsed().as_millis() as u64;
+        self.ponder.hit_after.store(hit_after, Ordering::Relaxed);
+        self.ponder.pondering.store(false, Ordering::Release);
+    }
+
+    pub fn is_pondering(&self) -> bool {
+        self.ponder.pondering.load(Ordering::Acquire)
+    }
+
+    /// Whether the running search has to be aborted
+    pub fn out_of_time(&self) -> bool {
+        match self.hard_limit {
+            Some(limit) => !self.is_pondering() && self.budget_used() >= limit,
+            None => false,
+        }
+    }
+
+    /// Whether there is enough time left to be worth starting another depth
+    pub fn can_start_iteration(&self) -> bool {
+        match self.soft_limit {
+            Some(limit) => self.is_pondering() || self.budget_used() < limit,
+            None => true,
+        }
+    }
+
+    // Time spent on our own clock, which doesn't include pondering
+    fn budget_used(&self) -> Duration {
+        let hit_after = Duration::from_millis(self.ponder.hit_after.load(Ordering::Relaxed));
+        self.elapsed().saturating_sub(hit_after)
+    }
+
+    pub fn soft_limit(&self) -> Option<Duration> {
+        self.soft_limit
+    }
+
+    pub fn hard_limit(&self) -> Option<Duration> {
+        self.hard_limit
+    }
+}
@@ -1,21 +1,27 @@
-//! Evict carefully, but using a big table prevents problems. Storing
-//! upperbound and lowerbound and other crap is also important. This should probably include
-//! refutations in the future and other optimizations.
+//! Fixed size transposition table. The memory is allocated once, split into buckets of two
+//! slots: one keeps the deepest entry that hashes there, the other always takes the newest one.
+//! Storing upperbound and lowerbound and other crap is also important. Every entry also keeps
+//! the best move or refutation of its position, which is what the principal variation is read
+//! from. Entries remember the search they were written in, so old ones get replaced first.
 
-use log::debug;
-use std::collections::HashMap;
+use std::mem;
 
-use chess::Board;
+use chess::{Board, ChessMove};
 
-// TODO: use size in memory instead of absolute size
-const MAX_ENTRIES: usize = 100000000;
+use super::score::Score;
+
+/// Size of the table if nobody asks for something else
+pub const DEFAULT_SIZE_MB: usize = 64;
 
 #[allow(dead_code)]
 #[derive(Clone, Debug)]
 pub struct TransTableEntry {
     pub flag: Flag,
-    pub eval: f32,
+    /// Mate scores are stored relative to this position, see `Score::to_tt`
+    pub eval: Score,
     pub depth: i32,
+    /// Move that was best or caused the cutoff, searched first when the position comes up again
+    pub best_move: Option<ChessMove>,
 }
 
 #[allow(dead_code)]
@@ -27,86 +33,160 @@ pub enum Flag {
     None,
 }
 
+#[derive(Clone)]
+struct Slot {
+    key: u64,
+    generation: u8,
+    entry: TransTableEntry,
+}
+
+#[derive(Clone, Default)]
+struct Bucket {
+    depth_preferred: Option<Slot>,
+    always_replace: Option<Slot>,
+}
+
 pub struct TransTable {
-    /// Wrapper of transposition table so it associates hash with an entry
-    // Do NOT directly write
-    pub tt: HashMap<u64, TransTableEntry>,
+    buckets: Vec<Bucket>,
+    // Bumped for every search, entries from older searches are replaced first
+    generation: u8,
 }
 
 #[allow(dead_code)]
 impl TransTable {
-    /// add_entry needs to be by itself because it needs to check if it needs
-    /// to evict and or to insert.
-
     pub fn new() -> Self {
+        TransTable::with_size_mb(DEFAULT_SIZE_MB)
+    }
+
+    pub fn with_size_mb(size_mb: usize) -> Self {
         // Allocate all the memory at once because it's expensive af to do on the fly
         TransTable {
-            tt: HashMap::with_capacity(MAX_ENTRIES),
+            buckets: vec![Bucket::default(); bucket_count(size_mb)],
+            generation: 0,
         }
     }
 
-    pub fn add_entry(&mut self, board: Board, entry: TransTableEntry) {
-        // This takes care of the size of the table
-        if need_to_evict(&self.tt) {
-            evict(&mut self.tt);
-            // Evicted from trans table
-        }
+    /// Throws away all entries and reallocates the table with the new size
+    pub fn resize(&mut self, size_mb: usize) {
+        *self = TransTable::with_size_mb(size_mb);
+    }
 
-        self.tt.insert(board.get_hash(), entry);
+    pub fn clear(&mut self) {
+        self.buckets.fill(Bucket::default());
+        self.generation = 0;
     }
-}
 
-fn need_to_evict(table: &HashMap<u64, TransTableEntry>) -> bool {
-    // Simple function, exists for readability
-    table.len() > MAX_ENTRIES
-}
+    /// Marks the start of a new search, so entries of earlier ones age
+    pub fn new_search(&mut self) {
+        self.generation = self.generation.wrapping_add(1);
+    }
 
-fn evict(table: &mut HashMap<u64, TransTableEntry>) {
-    // Currently just evicts down to MAX_ENTRIES - 20% with lowest depth if too full.
-    debug!(
-        "Current size of transposition table: {} entries",
-        table.len()
-    );
-    let num_to_evict = table.len() - (MAX_ENTRIES - (MAX_ENTRIES / 5));
-    let mut num_evicted = 0;
-    let mut eviction_list: Vec<u64> = vec![];
-
-    debug!(
-        "Started attempting to evict {} entries from transposition table",
-        num_to_evict
-    );
-
-    debug!("Getting eviction list");
-    while num_evicted < num_to_evict {
-        least_depth_entries(table, &mut eviction_list);
-
-        while !eviction_list.is_empty() && num_evicted < num_to_evict {
-            table.remove(&eviction_list.pop().expect("This should not be possible"));
-            num_evicted += 1;
+    pub fn get(&self, hash: u64) -> Option<&TransTableEntry> {
+        let bucket = &self.buckets[self.index(hash)];
+
+        [&bucket.depth_preferred, &bucket.always_replace]
+            .into_iter()
+            .flatten()
+            .find(|slot| slot.key == hash)
+            .map(|slot| &slot.entry)
+    }
+
+    pub fn add_entry(&mut self, board: Board, entry: TransTableEntry) {
+        let key = board.get_hash();
+        let generation = self.generation;
+        let index = self.index(key);
+        let bucket = &mut self.buckets[index];
+        let slot = Slot {
+            key,
+            generation,
+            entry,
+        };
+
+        // The deep slot only gives way to entries that are at least as deep, unless what it
+        // holds is from an older search or about the same position
+        let replace_deep = match &bucket.depth_preferred {
+            Some(old) => {
+                old.key == key
+                    || old.generation != generation
+                    || slot.entry.depth >= old.entry.depth
+            }
+            None => true,
+        };
+
+        if replace_deep {
+            // The demoted entry still has a chance to be useful, unless it's the same position
+            match bucket.depth_preferred.replace(slot) {
+                Some(old) if old.key != key => bucket.always_replace = Some(old),
+                _ => {}
+            }
+        } else {
+            bucket.always_replace = Some(slot);
         }
     }
 
-    debug!("Evicted {} entries from transposition tables", num_to_evict);
-}
+    /// How full the table is in permille, from a sample of the first thousand buckets. Only
+    /// entries of the current search count, like UCI asks for.
+    pub fn hashfull(&self) -> usize {
+        hashfull(&self.buckets, |bucket| {
+            [&bucket.depth_preferred, &bucket.always_replace]
+                .into_iter()
+                .flatten()
+                .filter(|slot| slot.generation == self.generation)
+                .count()
+        })
+    }
 
-fn least_depth_entries(table: &HashMap<u64, TransTableEntry>, eviction_list: &mut Vec<u64>) {
-    let mut lowest_depth = i32::MAX;
+    /// Follows the best moves stored in the table from `board` onwards, see
+    /// `principal_variation`
+    pub fn principal_variation(&self, board: Board, max_length: usize) -> Vec<ChessMove> {
+        principal_variation(board, max_length, |hash| {
+            self.get(hash).and_then(|entry| entry.best_move)
+        })
+    }
 
-    // Find the lowest depth in the map
-    for trans_table_entry in table.values() {
-        if trans_table_entry.depth < lowest_depth {
-            lowest_depth = trans_table_entry.depth;
-        }
+    fn index(&self, hash: u64) -> usize {
+        (hash % self.buckets.len() as u64) as usize
     }
+}
+
+/// Permille of the slots in a sample of the first thousand `buckets` that `current` counts as
+/// written in the current search. Every bucket has two slots.
+pub(crate) fn hashfull<B>(buckets: &[B], current: impl Fn(&B) -> usize) -> usize {
+    let sample = &buckets[..buckets.len().min(1000)];
+    let used: usize = sample.iter().map(current).sum();
 
-    debug!("Found lowest depth value of {}", lowest_depth);
+    used * 1000 / (sample.len() * 2)
+}
 
-    // Returns all of keys of the lowest depth
-    for (board, trans_table_entry) in table {
-        if trans_table_entry.depth == lowest_depth {
-            eviction_list.push(*board);
+/// Follows the moves `best_move` finds for the hashes of the positions from `board` onwards.
+/// Stops at a missing move, a move that isn't legal anymore (hash collision) or a position that
+/// was already visited.
+pub(crate) fn principal_variation(
+    mut board: Board,
+    max_length: usize,
+    best_move: impl Fn(u64) -> Option<ChessMove>,
+) -> Vec<ChessMove> {
+    let mut pv = vec![];
+    let mut visited = vec![board.get_hash()];
+
+    while pv.len() < max_length {
+        let next = match best_move(board.get_hash()) {
+            Some(next) if board.legal(next) => next,
+            _ => break,
+        };
+
+        board = board.make_move_new(next);
+        pv.push(next);
+
+        if visited.contains(&board.get_hash()) {
+            break;
         }
+        visited.push(board.get_hash());
     }
 
-    debug!("Got {} keys of lowest depth.", eviction_list.len());
+    pv
+}
+
+fn bucket_count(size_mb: usize) -> usize {
+    (size_mb * 1024 * 1024 / mem::size_of::<Bucket>()).max(1)
 }
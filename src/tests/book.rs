@@ -0,0 +1,347 @@
+use crate::book::builder::{BookBuilder, BuildOptions, ResultFilter};
+use crate::book::pgn::{parse_games, GameResult};
+use crate::book::{
+    decode_move, encode_move, polyglot_key, Book, BookEntry, BookSelection, POLYGLOT_RANDOM64,
+};
+use chess::{Board, ChessMove, Square};
+use std::str::FromStr;
+
+fn board(fen: &str) -> Board {
+    Board::from_str(fen).expect("Invalid FEN")
+}
+
+fn raw_move(source: &str, dest: &str) -> u16 {
+    let source = Square::from_str(source).unwrap();
+    let dest = Square::from_str(dest).unwrap();
+
+    dest.get_file().to_index() as u16
+        | (dest.get_rank().to_index() as u16) << 3
+        | (source.get_file().to_index() as u16) << 6
+        | (source.get_rank().to_index() as u16) << 9
+}
+
+fn book_bytes(entries: &[BookEntry]) -> Vec<u8> {
+    entries
+        .iter()
+        .flat_map(|entry| {
+            let mut bytes = entry.key.to_be_bytes().to_vec();
+            bytes.extend(entry.raw_move.to_be_bytes());
+            bytes.extend(entry.weight.to_be_bytes());
+            bytes.extend(entry.learn.to_be_bytes());
+            bytes
+        })
+        .collect()
+}
+
+fn entry(key: u64, raw_move: u16, weight: u16) -> BookEntry {
+    BookEntry {
+        key,
+        raw_move,
+        weight,
+        learn: 0,
+    }
+}
+
+#[test]
+fn key_layout() {
+    let random = &POLYGLOT_RANDOM64;
+
+    // Kings are the last pieces, white ones right after black ones. Then comes the side to move.
+    let kings = board("4k3/8/8/8/8/8/8/4K3 w - - 0 1");
+    assert_eq!(
+        polyglot_key(&kings),
+        random[64 * 11 + 4] ^ random[64 * 10 + 60] ^ random[780]
+    );
+
+    let castling = board("r3k2r/8/8/8/8/8/8/R3K2R w Kq - 0 1");
+    let no_castling = board("r3k2r/8/8/8/8/8/8/R3K2R w - - 0 1");
+    assert_eq!(
+        polyglot_key(&castling) ^ polyglot_key(&no_castling),
+        random[768] ^ random[771]
+    );
+}
+
+#[test]
+fn real_keys_match_the_specification() {
+    // The test positions of the Polyglot book format, reached with e4 d5 e5 f5 Ke2 Kf7 and with
+    // a4 b5 h4 b4 c4 bxc3 Ra3
+    let keys = [
+        (
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            0x463b96181691fc9c,
+        ),
+        (
+            "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1",
+            0x823c9b50fd114196,
+        ),
+        (
+            "rnbqkbnr/ppp1pppp/8/3p4/4P3/8/PPPP1PPP/RNBQKBNR w KQkq d6 0 2",
+            0x0756b94461c50fb0,
+        ),
+        (
+            "rnbqkbnr/ppp1pppp/8/3pP3/8/8/PPPP1PPP/RNBQKBNR b KQkq - 0 2",
+            0x662fafb965db29d4,
+        ),
+        (
+            "rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 3",
+            0x22a48b5a8e47ff78,
+        ),
+        (
+            "rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPPKPPP/RNBQ1BNR b kq - 0 3",
+            0x652a607ca3f242c1,
+        ),
+        (
+            "rnbq1bnr/ppp1pkpp/8/3pPp2/8/8/PPPPKPPP/RNBQ1BNR w - - 0 4",
+            0x00fdd303c946bdd9,
+        ),
+        (
+            "rnbqkbnr/p1pppppp/8/8/PpP4P/8/1P1PPPP1/RNBQKBNR b KQkq c3 0 3",
+            0x3c8123ea7b067637,
+        ),
+        (
+            "rnbqkbnr/p1pppppp/8/8/P6P/R1p5/1P1PPPP1/1NBQKBNR b Kkq - 0 4",
+            0x5c3f9b829b279560,
+        ),
+    ];
+
+    for (fen, key) in keys {
+        assert_eq!(polyglot_key(&board(fen)), key, "{}", fen);
+    }
+}
+
+#[test]
+fn en_passant_only_counts_with_a_capture() {
+    let random = &POLYGLOT_RANDOM64;
+
+    let nobody_can_take = board("rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1");
+    let same_position = board("rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq - 0 1");
+    assert_eq!(polyglot_key(&nobody_can_take), polyglot_key(&same_position));
+
+    let can_take = board("rnbqkbnr/ppp1pppp/8/8/3pP3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1");
+    let too_late = board("rnbqkbnr/ppp1pppp/8/8/3pP3/8/PPPP1PPP/RNBQKBNR b KQkq - 0 1");
+    assert_eq!(
+        polyglot_key(&can_take) ^ polyglot_key(&too_late),
+        random[772 + 4]
+    );
+}
+
+#[test]
+fn castling_is_written_as_taking_the_rook() {
+    let board = board("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1");
+
+    assert_eq!(
+        decode_move(&board, raw_move("e1", "h1")),
+        Some(ChessMove::from_str("e1g1").unwrap())
+    );
+    assert_eq!(
+        decode_move(&board, raw_move("e1", "a1")),
+        Some(ChessMove::from_str("e1c1").unwrap())
+    );
+    // A move of another position, e.g. after a key collision, is no move at all
+    assert_eq!(decode_move(&board, raw_move("e2", "e4")), None);
+}
+
+#[test]
+fn moves_are_found_and_picked_by_weight() {
+    let start = Board::default();
+    let key = polyglot_key(&start);
+    let bytes = book_bytes(&[
+        entry(key - 1, raw_move("a2", "a3"), 100),
+        entry(key, raw_move("e2", "e4"), 3),
+        entry(key, raw_move("d2", "d4"), 1),
+        entry(key + 1, raw_move("h2", "h3"), 100),
+    ]);
+
+    let mut book = Book::from_bytes(&bytes, BookSelection::Best).expect("Valid book");
+    assert_eq!(book.entries(key).len(), 2);
+    assert_eq!(
+        book.pick(&start),
+        Some(ChessMove::from_str("e2e4").unwrap())
+    );
+
+    book.set_selection(BookSelection::Weighted);
+    let picks: Vec<String> = (0..200)
+        .map(|_| book.pick(&start).unwrap().to_string())
+        .collect();
+    assert!(picks.iter().all(|pick| pick == "e2e4" || pick == "d2d4"));
+    assert!(picks.iter().any(|pick| pick == "d2d4"));
+    assert!(picks.iter().filter(|pick| *pick == "e2e4").count() > 100);
+
+    // Out of book
+    let after_e4 = start.make_move_new(ChessMove::from_str("e2e4").unwrap());
+    assert_eq!(book.pick(&after_e4), None);
+}
+
+#[test]
+fn broken_books_are_rejected() {
+    let unsorted = book_bytes(&[entry(2, 0, 1), entry(1, 0, 1)]);
+    assert!(Book::from_bytes(&unsorted, BookSelection::Best).is_err());
+    assert!(Book::from_bytes(&[0; 17], BookSelection::Best).is_err());
+}
+
+const GAMES: &str = r#"[Event "First"]
+[Result "1-0"]
+
+1. e4 {best by test} e5 2. Nf3 (2. f4 exf4) Nc6 $1 3. Bc4 Nf6?! 4. 0-0 Bc5
+5. c3 d6 1-0
+
+[Event "Second"]
+[Result "0-1"]
+
+1. e4 c5; the Sicilian
+2. Nf3 d6 0-1
+
+[Event "Third"]
+[FEN "8/P7/8/8/8/8/8/k3K3 w - - 0 1"]
+
+1. a8=Q+ Kb1 *
+"#;
+
+#[test]
+fn pgn_games_are_split_and_cleaned() {
+    let games = parse_games(GAMES);
+    assert_eq!(games.len(), 3);
+
+    assert_eq!(games[0].result, Some(GameResult::WhiteWins));
+    assert_eq!(
+        games[0].moves,
+        ["e4", "e5", "Nf3", "Nc6", "Bc4", "Nf6?!", "0-0", "Bc5", "c3", "d6"]
+    );
+    assert_eq!(games[1].result, Some(GameResult::BlackWins));
+    assert_eq!(games[1].moves, ["e4", "c5", "Nf3", "d6"]);
+    assert_eq!(games[2].result, None);
+
+    let castling = games[0].positions(usize::MAX).expect("Legal game")[6].1;
+    assert_eq!(castling, ChessMove::from_str("e1g1").unwrap());
+    let promotion = games[2].positions(usize::MAX).expect("Legal game")[0].1;
+    assert_eq!(promotion, ChessMove::from_str("a7a8q").unwrap());
+}
+
+#[test]
+fn moves_are_packed_the_way_they_are_read() {
+    let board = board("r3k2r/1P6/8/8/8/8/8/R3K2R w KQkq - 0 1");
+
+    for chess_move in ["e1g1", "e1c1", "b7a8n", "b7b8q", "a1a8"] {
+        let chess_move = ChessMove::from_str(chess_move).unwrap();
+        assert_eq!(
+            decode_move(&board, encode_move(&board, chess_move)),
+            Some(chess_move)
+        );
+    }
+    assert_eq!(
+        encode_move(&board, ChessMove::from_str("e1g1").unwrap()),
+        raw_move("e1", "h1")
+    );
+}
+
+#[test]
+fn built_books_read_back() {
+    let mut builder = BookBuilder::new(BuildOptions::default());
+    for game in parse_games(GAMES) {
+        builder.add_game(&game).expect("Legal game");
+    }
+
+    let book = Book::from_bytes(&builder.to_bytes(), BookSelection::Best).expect("Valid book");
+    let start = Board::default();
+    assert_eq!(
+        book.moves(&start),
+        [(ChessMove::from_str("e2e4").unwrap(), 2)]
+    );
+
+    // The winner's reply comes first
+    let after_e4 = start.make_move_new(ChessMove::from_str("e2e4").unwrap());
+    assert_eq!(
+        book.moves(&after_e4),
+        [(ChessMove::from_str("c7c5").unwrap(), 2)]
+    );
+
+    // Games without a result count as draws
+    let promotion = board("8/P7/8/8/8/8/8/k3K3 w - - 0 1");
+    assert_eq!(
+        book.moves(&promotion),
+        [(ChessMove::from_str("a7a8q").unwrap(), 1)]
+    );
+}
+
+#[test]
+fn builder_options_filter_moves() {
+    let games = parse_games(GAMES);
+    let start = Board::default();
+    let after_e4 = start.make_move_new(ChessMove::from_str("e2e4").unwrap());
+    let build = |options: BuildOptions| {
+        let mut builder = BookBuilder::new(options);
+        for game in &games {
+            builder.add_game(game).expect("Legal game");
+        }
+        Book::from_bytes(&builder.to_bytes(), BookSelection::Best).expect("Valid book")
+    };
+
+    // Only the first ply
+    let book = build(BuildOptions {
+        max_plies: 1,
+        ..BuildOptions::default()
+    });
+    assert_eq!(book.moves(&start).len(), 1);
+    assert!(book.moves(&after_e4).is_empty());
+
+    // Only e4 was played in both games
+    let book = build(BuildOptions {
+        min_count: 2,
+        ..BuildOptions::default()
+    });
+    assert_eq!(book.moves(&start).len(), 1);
+    assert!(book.moves(&after_e4).is_empty());
+
+    // e4 lost one of the games, the winners keep it anyway
+    let book = build(BuildOptions {
+        results: ResultFilter::Wins,
+        ..BuildOptions::default()
+    });
+    assert_eq!(
+        book.moves(&start),
+        [(ChessMove::from_str("e2e4").unwrap(), 2)]
+    );
+    assert_eq!(book.moves(&after_e4).len(), 1);
+    let promotion = board("8/P7/8/8/8/8/8/k3K3 w - - 0 1");
+    assert!(book.moves(&promotion).is_empty());
+}
+
+#[test]
+fn built_books_have_real_keys() {
+    // The game the test positions of the Polyglot book format come from
+    let games = parse_games("[Result \"1/2-1/2\"]\n\n1. e4 d5 2. e5 f5 3. Ke2 Kf7 1/2-1/2\n");
+    let mut builder = BookBuilder::new(BuildOptions::default());
+    builder.add_game(&games[0]).expect("Legal game");
+
+    let keys_and_moves: Vec<(u64, u16)> = builder
+        .entries()
+        .iter()
+        .map(|entry| (entry.key, entry.raw_move))
+        .collect();
+    assert_eq!(
+        keys_and_moves,
+        [
+            (0x0756b94461c50fb0, raw_move("e4", "e5")),
+            (0x22a48b5a8e47ff78, raw_move("e1", "e2")),
+            (0x463b96181691fc9c, raw_move("e2", "e4")),
+            (0x652a607ca3f242c1, raw_move("e8", "f7")),
+            (0x662fafb965db29d4, raw_move("f7", "f5")),
+            (0x823c9b50fd114196, raw_move("d7", "d5")),
+        ]
+    );
+}
+
+#[test]
+fn moves_after_the_ply_limit_are_not_replayed() {
+    let games = parse_games("[Result \"1/2-1/2\"]\n\n1. e4 e5 2. Qh5 Ke7 3. Qxe9 1/2-1/2\n");
+    let mut builder = BookBuilder::new(BuildOptions {
+        max_plies: 4,
+        ..BuildOptions::default()
+    });
+
+    builder
+        .add_game(&games[0])
+        .expect("The broken move is past the limit");
+    assert_eq!(builder.entries().len(), 4);
+    assert!(games[0].positions(5).is_err());
+}
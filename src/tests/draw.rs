@@ -0,0 +1,136 @@
+use std::str::FromStr;
+
+use chess::{Board, ChessMove};
+
+use crate::search;
+use crate::search::draw::{is_insufficient_material, GameHistory};
+use crate::search::score::Score;
+use crate::search::time_management::SearchLimits;
+use crate::search::SearchOptions;
+
+fn play(board: &mut Board, history: &mut GameHistory, moves: &[&str]) {
+    for move_text in moves {
+        let chess_move = ChessMove::from_str(move_text).unwrap();
+        history.push(board, chess_move);
+        *board = board.make_move_new(chess_move);
+    }
+}
+
+#[test]
+fn threefold_repetition_in_the_game() {
+    let mut board = Board::default();
+    let mut history = GameHistory::new(&board, 0);
+    let knight_dance = ["g1f3", "g8f6", "f3g1", "f6g8"];
+
+    play(&mut board, &mut history, &knight_dance);
+    assert!(!history.is_repetition(history.len()));
+    // Inside the search a single repetition is enough
+    assert!(history.is_repetition(1));
+
+    play(&mut board, &mut history, &knight_dance);
+    assert!(history.is_repetition(history.len()));
+}
+
+#[test]
+fn irreversible_moves_reset_the_halfmove_clock() {
+    let mut board = Board::default();
+    let mut history = GameHistory::new(&board, 0);
+
+    play(&mut board, &mut history, &["g1f3", "g8f6"]);
+    assert_eq!(history.halfmove_clock(), 2);
+    play(&mut board, &mut history, &["e2e4", "f6e4"]);
+    assert_eq!(history.halfmove_clock(), 0);
+    history.pop();
+    assert_eq!(history.halfmove_clock(), 0);
+    history.pop();
+    assert_eq!(history.halfmove_clock(), 2);
+}
+
+#[test]
+fn fifty_move_rule() {
+    let mut board = Board::from_str("8/8/4k3/8/8/4K3/8/R7 w - - 99 80").unwrap();
+    let mut history = GameHistory::new(&board, 99);
+    assert!(!history.is_draw(&board, history.len()));
+
+    play(&mut board, &mut history, &["a1a2"]);
+    assert!(history.is_draw(&board, history.len()));
+}
+
+#[test]
+fn insufficient_material() {
+    let draws = [
+        "8/8/4k3/8/8/4K3/8/8 w - - 0 1",
+        "8/8/4k3/8/8/4K3/8/6N1 w - - 0 1",
+        "8/8/4k3/8/8/4K3/8/5b2 w - - 0 1",
+        "8/3b4/4k3/8/8/4K3/8/5B2 w - - 0 1",
+    ];
+    let not_draws = [
+        "8/8/4k3/8/8/4K3/4P3/8 w - - 0 1",
+        "8/8/4k3/8/8/4K3/8/5BN1 w - - 0 1",
+        "8/2b5/4k3/8/8/4K3/8/5B2 w - - 0 1",
+        "8/8/4k3/8/8/4K3/8/R7 w - - 0 1",
+    ];
+
+    for fen in draws {
+        assert!(
+            is_insufficient_material(&Board::from_str(fen).unwrap()),
+            "{}",
+            fen
+        );
+    }
+    for fen in not_draws {
+        assert!(
+            !is_insufficient_material(&Board::from_str(fen).unwrap()),
+            "{}",
+            fen
+        );
+    }
+}
+
+#[test]
+fn search_scores_fifty_move_draws() {
+    // An extra rook is worth nothing when every move ends the game
+    let board = Board::from_str("8/8/4k3/8/8/4K3/8/R7 w - - 99 80").unwrap();
+    let history = GameHistory::new(&board, 99);
+    let result = search::iterative_deepening_search(
+        board,
+        &SearchLimits::depth(4),
+        &SearchOptions::default(),
+        None,
+        None,
+        Some(&history),
+    );
+    assert_eq!(result.eval, Score::DRAW);
+
+    // Unless the move that runs out the clock mates
+    let board = Board::from_str("6k1/5ppp/8/8/8/8/8/R5K1 w - - 99 80").unwrap();
+    let history = GameHistory::new(&board, 99);
+    let result = search::iterative_deepening_search(
+        board,
+        &SearchLimits::depth(4),
+        &SearchOptions::default(),
+        None,
+        None,
+        Some(&history),
+    );
+    assert_eq!(result.eval, Score::mate_in(1));
+}
+
+#[test]
+fn null_moves_break_repetitions() {
+    let mut board = Board::default();
+    let mut history = GameHistory::new(&board, 0);
+    let pass = |board: &mut Board, history: &mut GameHistory| {
+        *board = board.null_move().unwrap();
+        history.push_null(board);
+    };
+
+    play(&mut board, &mut history, &["g1f3"]);
+    pass(&mut board, &mut history);
+    play(&mut board, &mut history, &["f3g1"]);
+    pass(&mut board, &mut history);
+
+    // Back to the starting position, but only by passing twice
+    assert_eq!(board, Board::default());
+    assert!(!history.is_repetition(0));
+}
@@ -0,0 +1,191 @@
+use crate::engine::Engine;
+use crate::search::draw::GameHistory;
+use crate::search::score::Score;
+use crate::search::shared_transposition_table::SharedTransTable;
+use crate::search::thread_pool::{SearchJob, ThreadPool};
+use crate::search::time_management::SearchLimits;
+use crate::search::{iterative_deepening_search, SearchOptions};
+use chess::{Board, ChessMove};
+use std::panic::{self, AssertUnwindSafe};
+use std::str::FromStr;
+use std::sync::atomic::AtomicBool;
+use std::sync::{mpsc, Arc};
+use std::thread;
+use std::time::Duration;
+
+#[test]
+fn threads_are_reused_across_moves() {
+    let mut engine = Engine::new();
+    let options = SearchOptions {
+        threads: 2,
+        ..SearchOptions::default()
+    };
+    let mut board = Board::default();
+    let mut history = GameHistory::new(&board, 0);
+
+    for _ in 0..4 {
+        let result = engine.search(board, history.clone(), &SearchLimits::depth(4), &options);
+        assert!(board.legal(result.best_move));
+
+        history.push(&board, result.best_move);
+        board = board.make_move_new(result.best_move);
+    }
+}
+
+#[test]
+fn stop_reports_the_result() {
+    let mut engine = Engine::new();
+    let board = Board::default();
+    let limits = SearchLimits {
+        infinite: true,
+        ..SearchLimits::default()
+    };
+    let (sender, receiver) = mpsc::channel();
+
+    engine.go(
+        board,
+        GameHistory::new(&board, 0),
+        &limits,
+        &SearchOptions::default(),
+        move |result| sender.send(result.best_move).unwrap(),
+    );
+    thread::sleep(Duration::from_millis(100));
+    let result = engine.stop().expect("The search was started");
+
+    // Reported exactly once, before `stop` returned
+    assert_eq!(receiver.try_recv(), Ok(result.best_move));
+    assert!(receiver.try_recv().is_err());
+    assert!(board.legal(result.best_move));
+}
+
+#[test]
+fn pondering_waits_for_ponderhit() {
+    let mut engine = Engine::new();
+    let board = Board::default();
+    let limits = SearchLimits {
+        ponder: true,
+        ..SearchLimits::depth(3)
+    };
+    let (sender, receiver) = mpsc::channel();
+
+    engine.go(
+        board,
+        GameHistory::new(&board, 0),
+        &limits,
+        &SearchOptions::default(),
+        move |result| sender.send(result.best_move).unwrap(),
+    );
+
+    // The depth is done long before, but the move is only reported after the ponderhit
+    thread::sleep(Duration::from_millis(300));
+    assert!(receiver.try_recv().is_err());
+
+    engine.ponderhit();
+    let result = engine.wait().expect("The search was started");
+    assert_eq!(receiver.try_recv(), Ok(result.best_move));
+    assert_eq!(result.depth, 3);
+}
+
+#[test]
+fn depths_below_one_search_one_ply() {
+    let mut engine = Engine::new();
+    let board = Board::from_str("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1").unwrap();
+
+    for depth in [0, -3] {
+        let result = engine.search(
+            board,
+            GameHistory::new(&board, 0),
+            &SearchLimits::depth(depth),
+            &SearchOptions::default(),
+        );
+        assert_eq!(result.depth, 1);
+        assert_eq!(result.best_move, ChessMove::from_str("a1a8").unwrap());
+    }
+}
+
+#[test]
+fn positions_without_moves_are_not_searched() {
+    // Fool's mate
+    let mated =
+        Board::from_str("rnb1kbnr/pppp1ppp/8/4p3/6Pq/5P2/PPPPP2P/RNBQKBNR w KQkq - 1 3").unwrap();
+    let mut engine = Engine::new();
+    let (sender, receiver) = mpsc::channel();
+
+    engine.go(
+        mated,
+        GameHistory::new(&mated, 0),
+        &SearchLimits::depth(4),
+        &SearchOptions::default(),
+        move |result| sender.send(result.eval).unwrap(),
+    );
+    let result = engine.wait().expect("The result is reported");
+    assert_eq!(result.eval, Score::mated_in(0));
+    assert!(result.pv.is_empty());
+    assert_eq!(receiver.try_recv(), Ok(result.eval));
+
+    let stalemate = Board::from_str("7k/5Q2/6K1/8/8/8/8/8 b - - 0 1").unwrap();
+    let result = iterative_deepening_search(
+        stalemate,
+        &SearchLimits::depth(4),
+        &SearchOptions::default(),
+        None,
+        None,
+        None,
+    );
+    assert_eq!(result.eval, Score::DRAW);
+    assert_eq!(result.depth, 0);
+}
+
+#[test]
+fn panics_reach_the_waiter() {
+    let mut engine = Engine::new();
+    let options = SearchOptions {
+        threads: 2,
+        ..SearchOptions::default()
+    };
+    let board = Board::default();
+
+    engine.go(
+        board,
+        GameHistory::new(&board, 0),
+        &SearchLimits::depth(2),
+        &options,
+        |_| panic!("Reporting failed"),
+    );
+    let waited = panic::catch_unwind(AssertUnwindSafe(|| engine.wait()));
+    let payload = waited.expect_err("The panic is passed on");
+    assert_eq!(payload.downcast_ref::<&str>(), Some(&"Reporting failed"));
+
+    // The workers survive and take the next search
+    let result = engine.search(
+        board,
+        GameHistory::new(&board, 0),
+        &SearchLimits::depth(2),
+        &options,
+    );
+    assert!(board.legal(result.best_move));
+}
+
+#[test]
+fn workers_report_when_their_search_panics() {
+    let pool = ThreadPool::new(2);
+    let job = |board: Board| {
+        SearchJob::new(
+            board,
+            &SearchLimits::depth(2),
+            SearchOptions::default(),
+            Arc::new(SharedTransTable::new()),
+            Arc::new(AtomicBool::new(false)),
+            GameHistory::new(&board, 0),
+        )
+    };
+
+    // The workers expect a move to search, the pool doesn't screen positions like the engine does
+    let stalemate = Board::from_str("7k/5Q2/6K1/8/8/8/8/8 b - - 0 1").unwrap();
+    pool.start(job(stalemate), None);
+    assert!(panic::catch_unwind(AssertUnwindSafe(|| pool.wait())).is_err());
+
+    let board = Board::default();
+    let result = pool.search(job(board));
+    assert!(board.legal(result.best_move));
+}
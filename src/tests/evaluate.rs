@@ -0,0 +1,138 @@
+use std::str::FromStr;
+
+use chess::Board;
+
+use crate::search::evaluate::king_safety::evaluate_king_safety;
+use crate::search::evaluate::mobility::evaluate_mobility;
+use crate::search::evaluate::pawns::{evaluate_pawns, PawnTable};
+use crate::search::evaluate::{evaluate, game_phase, TaperedScore, MAX_PHASE};
+use crate::search::score::Score;
+
+fn pawns(fen: &str) -> TaperedScore {
+    evaluate_pawns(&Board::from_str(fen).unwrap(), &mut PawnTable::new())
+}
+
+fn king_safety(fen: &str) -> TaperedScore {
+    evaluate_king_safety(&Board::from_str(fen).unwrap())
+}
+
+#[test]
+fn starting_position_is_balanced() {
+    let board = Board::default();
+
+    assert_eq!(game_phase(&board), MAX_PHASE);
+    assert_eq!(evaluate(board, &mut PawnTable::new()), Score::DRAW);
+}
+
+#[test]
+fn mirrored_positions_evaluate_the_same_for_the_other_side() {
+    let white =
+        Board::from_str("r3k2r/pp3ppp/2n5/3qp3/8/2N2N2/PPP2PPP/R2QK2R w KQkq - 0 1").unwrap();
+    let black =
+        Board::from_str("r2qk2r/ppp2ppp/2n2n2/8/3QP3/2N5/PP3PPP/R3K2R b KQkq - 0 1").unwrap();
+
+    assert_eq!(
+        evaluate(white, &mut PawnTable::new()),
+        -evaluate(black, &mut PawnTable::new())
+    );
+}
+
+#[test]
+fn phase_follows_the_material_left() {
+    let pawn_ending = Board::from_str("8/5k2/4p3/8/3P4/8/5K2/8 w - - 0 1").unwrap();
+    let rook_ending = Board::from_str("8/5k2/4p3/8/3P4/8/5K2/R6r w - - 0 1").unwrap();
+
+    assert_eq!(game_phase(&pawn_ending), 0);
+    assert_eq!(game_phase(&rook_ending), 4);
+    assert_eq!(TaperedScore::new(100, 20).taper(MAX_PHASE), 100);
+    assert_eq!(TaperedScore::new(100, 20).taper(0), 20);
+    assert_eq!(TaperedScore::new(100, 20).taper(MAX_PHASE / 2), 60);
+}
+
+#[test]
+fn king_centralizes_in_the_endgame() {
+    let central = Board::from_str("8/8/4k3/8/8/4K3/4P3/8 w - - 0 1").unwrap();
+    let cornered = Board::from_str("8/8/4k3/8/8/8/4P3/7K w - - 0 1").unwrap();
+
+    assert!(evaluate(central, &mut PawnTable::new()) > evaluate(cornered, &mut PawnTable::new()));
+}
+
+#[test]
+fn doubled_and_isolated_pawns_are_penalized() {
+    // White's a-pawns are doubled and both isolated, black's a-pawn is isolated
+    assert_eq!(
+        pawns("4k3/p7/8/8/8/P7/P7/4K3 w - - 0 1"),
+        TaperedScore::new(-20, -35)
+    );
+}
+
+#[test]
+fn backward_and_passed_pawns() {
+    // d3 is backward since e5 controls d4, c4 is passed and e5 is isolated
+    assert_eq!(
+        pawns("4k3/8/8/4p3/2P5/3P4/8/4K3 w - - 0 1"),
+        TaperedScore::new(17, 30)
+    );
+}
+
+#[test]
+fn blocked_passed_pawns_are_worth_less() {
+    let free = pawns("k7/8/8/8/4P3/8/8/K7 w - - 0 1");
+    let blocked = pawns("k7/8/4n3/8/4P3/8/8/K7 w - - 0 1");
+
+    assert!(free.mg > blocked.mg && free.eg > blocked.eg);
+    // Far advanced passers are worth more
+    assert!(pawns("k7/8/4P3/8/8/8/8/K7 w - - 0 1").eg > free.eg);
+}
+
+#[test]
+fn pawn_table_returns_the_same_structure() {
+    let mut table = PawnTable::new();
+    let board = Board::from_str("4k3/8/8/4p3/2P5/3P4/8/4K3 w - - 0 1").unwrap();
+    let other = Board::from_str("4k3/p7/8/8/8/P7/P7/4K3 w - - 0 1").unwrap();
+
+    let first = evaluate_pawns(&board, &mut table);
+    assert_eq!(
+        evaluate_pawns(&other, &mut table),
+        pawns("4k3/p7/8/8/8/P7/P7/4K3 w - - 0 1")
+    );
+    assert_eq!(evaluate_pawns(&board, &mut table), first);
+}
+
+#[test]
+fn king_shield_holes_are_penalized() {
+    let intact = king_safety("6k1/5ppp/8/8/8/8/5PPP/6K1 w - - 0 1");
+    let pushed = king_safety("6k1/5ppp/8/8/8/6P1/5P1P/6K1 w - - 0 1");
+    let open = king_safety("6k1/5ppp/8/8/8/8/5P1P/6K1 w - - 0 1");
+
+    assert_eq!(intact, TaperedScore::default());
+    assert!(pushed.mg < intact.mg);
+    assert!(open.mg < pushed.mg);
+    // Only matters in the middlegame
+    assert_eq!(open.eg, 0);
+}
+
+#[test]
+fn pieces_attacking_the_king_zone_are_dangerous() {
+    let attacked = king_safety("6k1/5ppp/8/8/8/5n1q/5PPP/6K1 w - - 0 1");
+    let far_away = king_safety("6k1/5ppp/8/q7/n7/8/5PPP/6K1 w - - 0 1");
+
+    assert!(attacked.mg < far_away.mg - 50);
+}
+
+#[test]
+fn centralized_pieces_are_more_mobile() {
+    let corner = evaluate_mobility(&Board::from_str("k7/8/8/8/8/8/8/N6K w - - 0 1").unwrap());
+    let center = evaluate_mobility(&Board::from_str("k7/8/8/8/3N4/8/8/7K w - - 0 1").unwrap());
+
+    assert!(center.mg > corner.mg && center.eg > corner.eg);
+}
+
+#[test]
+fn squares_attacked_by_pawns_do_not_count() {
+    // The pawn on c6 takes b5 away from the knight, the one on h6 doesn't cover any of its squares
+    let covered = evaluate_mobility(&Board::from_str("k7/8/2p5/8/3N4/8/8/7K w - - 0 1").unwrap());
+    let free = evaluate_mobility(&Board::from_str("k7/8/7p/8/3N4/8/8/7K w - - 0 1").unwrap());
+
+    assert_eq!(free - covered, TaperedScore::new(4, 4));
+}
@@ -0,0 +1,155 @@
+use crate::search::draw::GameHistory;
+use crate::search::score::Score;
+use crate::search::shared_transposition_table::SharedTransTable;
+use crate::search::time_management::{SearchLimits, TimeManager};
+use crate::search::transposition_table::{Flag, TransTableEntry};
+use crate::search::{singular_move, SearchContext, SearchOptions, MAX_LINE_EXTENSIONS};
+use chess::{Board, ChessMove, Color};
+use std::str::FromStr;
+use std::sync::atomic::AtomicBool;
+use std::sync::Arc;
+
+fn context_with(options: SearchOptions) -> SearchContext {
+    SearchContext::new(
+        options,
+        Arc::new(SharedTransTable::new()),
+        Arc::new(AtomicBool::new(false)),
+        TimeManager::new(&SearchLimits::default(), Color::White),
+        GameHistory::new(&Board::default(), 0),
+    )
+}
+
+fn all_extensions() -> SearchOptions {
+    SearchOptions {
+        recapture_extensions: true,
+        passed_pawn_extensions: true,
+        ..SearchOptions::default()
+    }
+}
+
+fn board(fen: &str) -> Board {
+    Board::from_str(fen).expect("Invalid FEN")
+}
+
+fn extension(context: &SearchContext, board: &Board, chess_move: &str, ply: i32) -> i32 {
+    let chess_move = ChessMove::from_str(chess_move).unwrap();
+    context.extension(
+        board,
+        &board.make_move_new(chess_move),
+        chess_move,
+        ply,
+        false,
+    )
+}
+
+fn hash_entry(chess_move: &str, eval: Score, depth: i32, flag: Flag) -> TransTableEntry {
+    TransTableEntry {
+        flag,
+        eval,
+        depth,
+        best_move: Some(ChessMove::from_str(chess_move).unwrap()),
+    }
+}
+
+#[test]
+fn checks_are_extended() {
+    let context = context_with(SearchOptions::default());
+    let position = board("4k3/8/8/8/8/8/8/R3K3 w - - 0 1");
+
+    assert_eq!(extension(&context, &position, "a1a8", 1), 1);
+    assert_eq!(extension(&context, &position, "a1a7", 1), 0);
+
+    let rook_a7 = ChessMove::from_str("a1a7").unwrap();
+    let singular = context.extension(
+        &position,
+        &position.make_move_new(rook_a7),
+        rook_a7,
+        1,
+        true,
+    );
+    assert_eq!(singular, 1);
+}
+
+#[test]
+fn recaptures_are_extended() {
+    let mut context = context_with(all_extensions());
+    // White took on d5 at ply 0
+    let before = board("4k3/8/2n1p3/3p4/1P6/8/3R4/4K3 w - - 0 1");
+    context.set_capture_square(0, &before, Some(ChessMove::from_str("d2d5").unwrap()));
+    let position = board("4k3/8/2n1p3/3R4/1P6/8/8/4K3 b - - 0 1");
+
+    assert_eq!(extension(&context, &position, "e6d5", 1), 1);
+    // Captures elsewhere and quiet moves to the square aren't recaptures
+    assert_eq!(extension(&context, &position, "c6b4", 1), 0);
+    assert_eq!(extension(&context, &position, "c6e5", 1), 0);
+    // Only right after the capture
+    assert_eq!(extension(&context, &position, "e6d5", 2), 0);
+
+    let mut without = context_with(SearchOptions::default());
+    without.set_capture_square(0, &before, Some(ChessMove::from_str("d2d5").unwrap()));
+    assert_eq!(extension(&without, &position, "e6d5", 1), 0);
+}
+
+#[test]
+fn pawn_pushes_to_the_seventh_rank_are_extended() {
+    let context = context_with(all_extensions());
+    let white = board("4k3/8/1P6/P7/8/8/8/4K3 w - - 0 1");
+    let black = board("4k3/8/8/8/8/2p5/8/4K3 b - - 0 1");
+
+    assert_eq!(extension(&context, &white, "b6b7", 1), 1);
+    assert_eq!(extension(&context, &white, "a5a6", 1), 0);
+    assert_eq!(extension(&context, &black, "c3c2", 1), 1);
+
+    let without = context_with(SearchOptions::default());
+    assert_eq!(extension(&without, &white, "b6b7", 1), 0);
+}
+
+#[test]
+fn extensions_stop_at_the_line_budget() {
+    let mut context = context_with(SearchOptions::default());
+    let position = board("4k3/8/8/8/8/8/8/R3K3 w - - 0 1");
+
+    // A line of checks, extended until the budget is used up
+    for _ in 0..MAX_LINE_EXTENSIONS + 5 {
+        context.extensions += extension(&context, &position, "a1a8", 1);
+    }
+    assert_eq!(context.extensions, MAX_LINE_EXTENSIONS);
+}
+
+#[test]
+fn only_move_is_singular() {
+    // Taking the queen is the only move that doesn't lose the rook
+    let position = board("4k3/8/8/3q4/8/8/3R4/4K3 w - - 0 1");
+    let entry = hash_entry("d2d5", Score::cp(500), 6, Flag::Lowerbound);
+
+    let singular = singular_move(
+        position,
+        &entry,
+        6,
+        1,
+        &mut context_with(SearchOptions::default()),
+    );
+    assert_eq!(singular, Some(ChessMove::from_str("d2d5").unwrap()));
+}
+
+#[test]
+fn alternatives_and_unreliable_entries_are_not_singular() {
+    let mut context = context_with(SearchOptions::default());
+
+    // The other rook takes the queen just as well
+    let two_rooks = board("4k3/8/8/R2q4/8/8/3R4/4K3 w - - 0 1");
+    let entry = hash_entry("d2d5", Score::cp(500), 6, Flag::Lowerbound);
+    assert_eq!(singular_move(two_rooks, &entry, 6, 1, &mut context), None);
+
+    let position = board("4k3/8/8/3q4/8/8/3R4/4K3 w - - 0 1");
+    for entry in [
+        // Too shallow
+        hash_entry("d2d5", Score::cp(500), 2, Flag::Lowerbound),
+        // Could be worse than it says
+        hash_entry("d2d5", Score::cp(500), 6, Flag::Upperbound),
+        // Margins mean nothing next to a mate
+        hash_entry("d2d5", Score::mate_in(5), 6, Flag::Lowerbound),
+    ] {
+        assert_eq!(singular_move(position, &entry, 6, 1, &mut context), None);
+    }
+}
@@ -1,14 +1,45 @@
 #![cfg(test)]
+mod book;
+mod draw;
+mod engine;
+mod evaluate;
+mod extensions;
+mod move_ordering;
+mod pruning;
+mod score;
+mod see;
+mod time_management;
+mod transposition_table;
+mod uci;
+
 use crate::search;
-use chess::{Board};
+use crate::search::draw::GameHistory;
+use crate::search::time_management::{SearchLimits, TimeManager};
+use crate::search::score::Score;
+use crate::search::shared_transposition_table::SharedTransTable;
+use crate::search::{RootSearch, SearchContext, SearchOptions, SearchResult};
+use chess::{Board, Color};
 use std::str::FromStr;
+use std::sync::atomic::AtomicBool;
+use std::sync::Arc;
+
+fn search_to_depth(board: Board, depth: i32) -> SearchResult {
+    search::iterative_deepening_search(
+        board,
+        &SearchLimits::depth(depth),
+        &SearchOptions::default(),
+        None,
+        None,
+        None,
+    )
+}
 
 #[test]
 fn vienna_gambit() {
     // Tests the response of engine after e5 from vienna gambit accepted.
     let board = Board::from_str("rnbqkb1r/pppp1ppp/5n2/4P3/5p2/2N5/PPPP2PP/R1BQKBNR b KQkq - 0 4")
         .expect("Invalid FEN");
-    let best_move = search::iterative_deepening_search(board, 8, None);
+    let best_move = search_to_depth(board, 8).best_move;
     // Two possible variations, both are correct
     assert!(best_move.to_string() == "f6g8" || best_move.to_string() == "d8e7");
 }
@@ -18,7 +49,7 @@ fn mate1() {
     // Makes sure it doesn't miss mate in ones
     let board =
         Board::from_str("4k3/2np1p2/4p1Pn/2q5/2P4P/5b2/2r2R2/6K1 b - - 0 34").expect("Invalid FEN");
-    let best_move = search::iterative_deepening_search(board, 6, None);
+    let best_move = search_to_depth(board, 6).best_move;
     assert!(best_move.to_string() == "c5f2");
 }
 
@@ -26,7 +57,7 @@ fn mate1() {
 fn backrank2() {
     // Tests the response of engine when faced with backrank checkmate opportunity
     let board = Board::from_str("2R2rk1/4pppp/8/8/8/8/6K1/2R5 w - - 0 1").expect("Invalid FEN");
-    let best_move = search::iterative_deepening_search(board, 6, None);
+    let best_move = search_to_depth(board, 6).best_move;
     assert!(best_move.to_string() == "c8f8");
 }
 
@@ -34,7 +65,7 @@ fn backrank2() {
 fn morphy2() {
     // Tests the response of engine when faced with backrank checkmate opportunity
     let board = Board::from_str("kbK5/pp6/1P6/8/8/8/8/R7 w - -").expect("Invalid FEN");
-    let best_move = search::iterative_deepening_search(board, 6, None);
+    let best_move = search_to_depth(board, 6).best_move;
     assert!(best_move.to_string() == "a1a6");
 }
 
@@ -43,7 +74,7 @@ fn graucolle3() {
     // Tests the response of engine when faced with a mate in 3
     let board =
         Board::from_str("1k5r/pP3ppp/3p2b1/1BN1n3/1Q2P3/P1B5/KP3P1P/7q w - - 1 0").expect("Invalid FEN");
-    let best_move = search::iterative_deepening_search(board, 7, None);
+    let best_move = search_to_depth(board, 7).best_move;
     assert!(best_move.to_string() == "c5a6");
 }
 
@@ -52,6 +83,140 @@ fn kasperov4() {
     // Tests the response of engine when faced with a tough puzzle
     let board =
         Board::from_str("4k2r/1R3R2/p3p1pp/4b3/1BnNr3/8/P1P5/5K2 w - - 1 0").expect("Invalid FEN");
-    let best_move = search::iterative_deepening_search(board, 8, None);
+    let best_move = search_to_depth(board, 8).best_move;
     assert!(best_move.to_string() == "f7e7");
 }
+
+#[test]
+fn quiescence_resolves_captures() {
+    let tt = Arc::new(SharedTransTable::new());
+    let timer = TimeManager::new(&SearchLimits::default(), Color::White);
+    let history = GameHistory::new(&Board::default(), 0);
+    let mut context = SearchContext::new(
+        SearchOptions::default(),
+        tt,
+        Arc::new(AtomicBool::new(false)),
+        timer,
+        history,
+    );
+
+    // The hanging queen is taken even though the search ran out of depth
+    let board = Board::from_str("4k3/8/8/3q4/8/8/3R4/4K3 w - - 0 1").expect("Invalid FEN");
+    let value = search::quiescence(board, 0, -Score::INFINITY, Score::INFINITY, &mut context);
+    assert!(value > Score::cp(400));
+
+    // Taking the defended pawn loses the queen, so standing pat is better
+    let board = Board::from_str("4k3/8/2p5/3p4/8/8/3Q4/4K3 w - - 0 1").expect("Invalid FEN");
+    let value = search::quiescence(board, 0, -Score::INFINITY, Score::INFINITY, &mut context);
+    assert!(value > Score::cp(600) && value < Score::cp(800));
+}
+
+#[test]
+fn principal_variation_is_playable() {
+    let board = Board::from_str("r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 2 3")
+        .expect("Invalid FEN");
+    let result = search_to_depth(board, 5);
+
+    assert_eq!(result.pv[0], result.best_move);
+    assert!(result.pv.len() > 1);
+
+    let mut position = board;
+    for chess_move in result.pv {
+        assert!(position.legal(chess_move));
+        position = position.make_move_new(chess_move);
+    }
+}
+
+#[test]
+fn mtdf_finds_the_same_mate() {
+    let board = Board::from_str("1k5r/pP3ppp/3p2b1/1BN1n3/1Q2P3/P1B5/KP3P1P/7q w - - 1 0")
+        .expect("Invalid FEN");
+    let options = SearchOptions {
+        root_search: RootSearch::Mtdf,
+        ..SearchOptions::default()
+    };
+    let mtdf = search::iterative_deepening_search(
+        board,
+        &SearchLimits::depth(7),
+        &options,
+        None,
+        None,
+        None,
+    );
+    let pvs = search_to_depth(board, 7);
+
+    assert_eq!(mtdf.eval, Score::mate_in(5));
+    assert_eq!(pvs.eval, mtdf.eval);
+}
+
+#[test]
+fn helpers_are_joined_and_agree_on_the_mate() {
+    let board = Board::from_str("1k5r/pP3ppp/3p2b1/1BN1n3/1Q2P3/P1B5/KP3P1P/7q w - - 1 0")
+        .expect("Invalid FEN");
+    let options = SearchOptions {
+        threads: 4,
+        ..SearchOptions::default()
+    };
+    let stop_now = Arc::new(AtomicBool::new(false));
+    let result = search::iterative_deepening_search(
+        board,
+        &SearchLimits::depth(7),
+        &options,
+        None,
+        Some(stop_now.clone()),
+        None,
+    );
+
+    assert_eq!(result.best_move.to_string(), "c5a6");
+    assert_eq!(result.eval, Score::mate_in(5));
+    assert!(result.depth <= 7);
+    // No thread is left holding the flag once the search returned
+    assert_eq!(Arc::strong_count(&stop_now), 1);
+}
+
+#[test]
+fn optional_extensions_keep_the_mate() {
+    // Same mate in 3 as graucolle3, the extra extensions must not get in the way
+    let board = Board::from_str("1k5r/pP3ppp/3p2b1/1BN1n3/1Q2P3/P1B5/KP3P1P/7q w - - 1 0")
+        .expect("Invalid FEN");
+    let options = SearchOptions {
+        recapture_extensions: true,
+        passed_pawn_extensions: true,
+        ..SearchOptions::default()
+    };
+    let result = search::iterative_deepening_search(
+        board,
+        &SearchLimits::depth(7),
+        &options,
+        None,
+        None,
+        None,
+    );
+
+    assert_eq!(result.best_move.to_string(), "c5a6");
+    assert_eq!(result.eval, Score::mate_in(5));
+}
+
+#[test]
+fn quiet_mate_survives_futility_pruning() {
+    // Black is a rook and a bishop up, so white is far below alpha everywhere, but Kb6 or Kc7
+    // mates in 2. Neither the quiet king move nor the mate after it may be pruned.
+    let board = Board::from_str("k7/8/2K5/8/8/7R/ppp5/rbr5 w - - 0 1").expect("Invalid FEN");
+    let result = search_to_depth(board, 5);
+
+    assert!(["c6b6", "c6c7"].contains(&result.best_move.to_string().as_str()));
+    assert_eq!(result.eval, Score::mate_in(3));
+}
+
+#[test]
+fn advanced_pawns_turn_off_razoring() {
+    // Sixth and seventh rank as seen from the side to move
+    let advanced = |fen: &str| search::has_advanced_pawn(&Board::from_str(fen).unwrap());
+
+    assert!(advanced("kbK5/pp6/1P6/8/8/8/8/R7 w - - 0 1"));
+    assert!(advanced("r7/8/8/8/8/1p6/PP6/KBk5 b - - 0 1"));
+    assert!(advanced("4k3/8/8/8/8/8/p7/4K3 b - - 0 1"));
+    // Only the pawns of the side to move count
+    assert!(!advanced("r7/8/8/8/8/1p6/PP6/KBk5 w - - 0 1"));
+    assert!(!advanced("4k3/8/8/3P4/8/8/8/4K3 w - - 0 1"));
+}
@@ -0,0 +1,40 @@
+use crate::search::move_ordering::MoveOrdering;
+use chess::{Board, ChessMove};
+use std::str::FromStr;
+
+#[test]
+fn hash_move_then_captures_then_killers() {
+    // The rook can take the queen or the pawn, the knight can take the pawn
+    let board = Board::from_str("4k3/8/8/8/8/3p4/3R3q/2N1K3 w - - 0 1").expect("Invalid FEN");
+    let hash_move = ChessMove::from_str("e1f1").unwrap();
+    let killer = ChessMove::from_str("d2a2").unwrap();
+
+    let mut ordering = MoveOrdering::new();
+    ordering.record_cutoff(&board, killer, 3, 2);
+    let moves = ordering.order_moves(&board, Some(hash_move), 2);
+
+    let position = |text: &str| {
+        moves
+            .iter()
+            .position(|chess_move| *chess_move == ChessMove::from_str(text).unwrap())
+            .unwrap()
+    };
+
+    assert_eq!(moves[0], hash_move);
+    assert_eq!(moves[1], ChessMove::from_str("d2h2").unwrap());
+    // Same victim, so the less valuable attacker goes first
+    assert!(position("c1d3") < position("d2d3"));
+    assert_eq!(moves[4], killer);
+    assert!(ordering.is_killer(killer, 2));
+    assert!(!ordering.is_killer(killer, 3));
+}
+
+#[test]
+fn losing_captures_go_last() {
+    // Taking the pawn on d6 costs the rook, taking the one on h5 is free
+    let board = Board::from_str("4k3/2p5/3p4/7p/8/8/8/3RK2R w - - 0 1").expect("Invalid FEN");
+    let moves = MoveOrdering::new().order_moves(&board, None, 0);
+
+    assert_eq!(moves[0], ChessMove::from_str("h1h5").unwrap());
+    assert_eq!(moves[moves.len() - 1], ChessMove::from_str("d1d6").unwrap());
+}
@@ -0,0 +1,256 @@
+use super::search_to_depth;
+use crate::search::draw::GameHistory;
+use crate::search::score::Score;
+use crate::search::shared_transposition_table::SharedTransTable;
+use crate::search::time_management::{SearchLimits, TimeManager};
+use crate::search::{
+    self, is_quiet, late_move_pruned, late_move_reduction, negamax, null_move_cutoff, scout,
+    SearchContext, SearchOptions, LMP_MOVE_COUNTS, REDUCTIONS,
+};
+use chess::{Board, ChessMove, Color, MoveGen};
+use std::str::FromStr;
+use std::sync::atomic::AtomicBool;
+use std::sync::Arc;
+
+fn context() -> SearchContext {
+    context_with(Arc::new(SharedTransTable::new()))
+}
+
+fn context_with(tt: Arc<SharedTransTable>) -> SearchContext {
+    SearchContext::new(
+        SearchOptions::default(),
+        tt,
+        Arc::new(AtomicBool::new(false)),
+        TimeManager::new(&SearchLimits::default(), Color::White),
+        GameHistory::new(&Board::default(), 0),
+    )
+}
+
+fn board(fen: &str) -> Board {
+    Board::from_str(fen).expect("Invalid FEN")
+}
+
+// The positions are quiet, so this is their static evaluation
+fn static_eval(board: Board) -> Score {
+    search::quiescence(board, 0, -Score::INFINITY, Score::INFINITY, &mut context())
+}
+
+// Null window search of `board` with null moves only allowed below it
+fn search_without_null_move(board: Board, depth: i32, beta: Score) -> Score {
+    let mut context = context();
+    context.no_null_move = true;
+    negamax(board, depth, 1, beta - Score::cp(1), beta, &mut context)
+}
+
+#[test]
+fn null_move_cuts_off_when_far_ahead() {
+    let queen_up = board("4k3/8/8/8/8/8/3Q4/4K3 w - - 0 1");
+
+    let value = null_move_cutoff(
+        queen_up,
+        4,
+        1,
+        Score::DRAW,
+        static_eval(queen_up),
+        &mut context(),
+    );
+    assert!(value.is_some_and(|value| value >= Score::DRAW));
+}
+
+#[test]
+fn no_null_move_in_pawn_endgames() {
+    let pawn_up = board("4k3/ppp5/8/8/8/8/PPPP4/4K3 w - - 0 1");
+    let mut context = context();
+
+    let value = null_move_cutoff(
+        pawn_up,
+        4,
+        1,
+        Score::cp(-500),
+        static_eval(pawn_up),
+        &mut context,
+    );
+    assert_eq!(value, None);
+    // Not even tried
+    assert_eq!(context.nodes, 0);
+}
+
+#[test]
+fn no_null_move_right_after_a_null_move() {
+    let queen_up = board("4k3/8/8/8/8/8/3Q4/4K3 w - - 0 1");
+
+    let mut after_move = context();
+    negamax(queen_up, 4, 1, Score::cp(-1), Score::DRAW, &mut after_move);
+
+    let mut after_null_move = context();
+    after_null_move.no_null_move = true;
+    negamax(
+        queen_up,
+        4,
+        1,
+        Score::cp(-1),
+        Score::DRAW,
+        &mut after_null_move,
+    );
+
+    // Without the null move cutoff the moves have to be searched
+    assert!(after_move.nodes < after_null_move.nodes);
+    // Only the node right after the null move goes without
+    assert!(!after_null_move.no_null_move);
+}
+
+#[test]
+fn verification_catches_zugzwang() {
+    // Black to move has to give up the f5 pawn, the bishop is locked in. Passing would leave
+    // white to give up f4 instead.
+    let zugzwang = board("b7/1p6/1P6/2P1Kp2/5Pk1/8/8/8 b - - 0 1");
+    let beta = Score::cp(-40);
+    let static_eval = static_eval(zugzwang);
+
+    // Too shallow to be verified, the null move gets it wrong
+    assert!(null_move_cutoff(zugzwang, 4, 1, beta, static_eval, &mut context()).is_some());
+    assert!(search_without_null_move(zugzwang, 4, beta) < beta);
+
+    for depth in [5, 6] {
+        assert_eq!(
+            null_move_cutoff(zugzwang, depth, 1, beta, static_eval, &mut context()),
+            None
+        );
+        assert!(search_without_null_move(zugzwang, depth, beta) < beta);
+    }
+
+    // Unverified cutoffs make c7 look better than walking into the trebuchet
+    let before = board("b7/1p6/1P1K4/2P2p2/5Pk1/8/8/8 w - - 0 1");
+    let result = search_to_depth(before, 8);
+    assert_eq!(result.best_move, ChessMove::from_str("d6e5").unwrap());
+    assert!(result.eval > Score::DRAW);
+}
+
+#[test]
+fn reductions_grow_with_depth_and_move_number() {
+    // Rounded 0.5 + ln(depth) * ln(move number) / 2.5
+    for (depth, move_number, reduction) in [
+        (1, 40, 0),
+        (3, 3, 0),
+        (3, 4, 1),
+        (4, 3, 1),
+        (6, 10, 2),
+        (8, 20, 2),
+        (20, 30, 4),
+        (63, 63, 7),
+    ] {
+        assert_eq!(REDUCTIONS[depth][move_number], reduction);
+    }
+
+    for depth in 1..64 {
+        for move_number in 1..64 {
+            assert!(REDUCTIONS[depth][move_number] >= REDUCTIONS[depth - 1][move_number]);
+            assert!(REDUCTIONS[depth][move_number] >= REDUCTIONS[depth][move_number - 1]);
+        }
+    }
+}
+
+#[test]
+fn only_late_quiet_moves_are_reduced() {
+    assert_eq!(late_move_reduction(true, false, 8, 20, false), 2);
+    // Never so much that the move isn't searched at all
+    assert_eq!(late_move_reduction(true, false, 3, 40, false), 1);
+
+    // Captures, checks, killers and moves out of check get the full depth
+    assert_eq!(late_move_reduction(false, false, 8, 20, false), 0);
+    assert_eq!(late_move_reduction(true, true, 8, 20, false), 0);
+    assert_eq!(late_move_reduction(true, false, 8, 20, true), 0);
+    // So do the first moves and everything close to the leaves
+    assert_eq!(late_move_reduction(true, false, 8, 2, false), 0);
+    assert_eq!(late_move_reduction(true, false, 2, 20, false), 0);
+}
+
+#[test]
+fn only_late_quiet_moves_are_pruned() {
+    assert!(late_move_pruned(true, false, 2, 8, Score::DRAW));
+    assert!(!late_move_pruned(true, false, 2, 7, Score::DRAW));
+    assert!(!late_move_pruned(true, false, 4, 40, Score::DRAW));
+
+    assert!(!late_move_pruned(false, false, 2, 8, Score::DRAW));
+    assert!(!late_move_pruned(true, true, 2, 8, Score::DRAW));
+    // Getting mated, anything might help
+    assert!(!late_move_pruned(true, false, 2, 8, Score::mated_in(5)));
+}
+
+#[test]
+fn captures_and_checks_are_not_quiet() {
+    let position = board("4k3/8/8/3p4/8/8/8/3RK2R w - - 0 1");
+    let quiet = |chess_move: &str| {
+        let chess_move = ChessMove::from_str(chess_move).unwrap();
+        is_quiet(&position, &position.make_move_new(chess_move), chess_move)
+    };
+
+    assert!(quiet("h1h5"));
+    assert!(!quiet("d1d5"));
+    assert!(!quiet("h1h8"));
+}
+
+#[test]
+fn late_moves_are_pruned_near_the_leaves() {
+    // Nothing but quiet moves
+    let position = board("4k3/8/8/8/8/8/PPPPPPPP/RNBQKBNR w - - 0 1");
+    let static_eval = static_eval(position);
+    let tt = Arc::new(SharedTransTable::new());
+    let mut context = context_with(tt.clone());
+
+    // Wide enough that nothing else prunes
+    negamax(
+        position,
+        2,
+        1,
+        static_eval - Score::cp(500),
+        static_eval + Score::cp(500),
+        &mut context,
+    );
+
+    let searched = MoveGen::new_legal(&position)
+        .filter(|chess_move| {
+            tt.get(position.make_move_new(*chess_move).get_hash())
+                .is_some()
+        })
+        .count();
+    assert_eq!(searched, LMP_MOVE_COUNTS[2]);
+}
+
+#[test]
+fn late_moves_are_searched_shallower() {
+    let position = board("4k3/8/8/8/8/8/PPPPPPPP/RNBQKBNR w - - 0 1");
+    let tt = Arc::new(SharedTransTable::new());
+    let mut context = context_with(tt.clone());
+
+    negamax(
+        position,
+        6,
+        1,
+        -Score::INFINITY,
+        Score::INFINITY,
+        &mut context,
+    );
+
+    let depths: Vec<i32> = MoveGen::new_legal(&position)
+        .filter_map(|chess_move| tt.get(position.make_move_new(chess_move).get_hash()))
+        .map(|entry| entry.depth)
+        .collect();
+    assert!(depths.contains(&5));
+    assert!(depths.iter().any(|depth| *depth < 5));
+}
+
+#[test]
+fn reduced_moves_that_fail_high_get_the_full_depth() {
+    let position = board("rnbqkb1r/pppp1ppp/5n2/4P3/5p2/2N5/PPPP2PP/R1BQKBNR b KQkq - 0 4");
+    // Hangs the bishop to b2xa3, which two plies don't see
+    let bishop_a3 = position.make_move_new(ChessMove::from_str("f8a3").unwrap());
+    let alpha = Score::cp(-255);
+    let beta = alpha + Score::cp(1);
+
+    let reduced = -negamax(bishop_a3, 1, 1, -beta, -alpha, &mut context());
+    assert!(reduced > alpha);
+
+    let value = scout(bishop_a3, 4, 2, 0, alpha, beta, &mut context());
+    assert!(value <= alpha);
+}
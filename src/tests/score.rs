@@ -0,0 +1,32 @@
+use crate::search::score::Score;
+
+#[test]
+fn displays_centipawns_and_mates() {
+    assert_eq!(Score::cp(-35).to_string(), "cp -35");
+    assert_eq!(Score::mate_in(1).to_string(), "mate 1");
+    assert_eq!(Score::mate_in(5).to_string(), "mate 3");
+    assert_eq!(Score::mated_in(2).to_string(), "mate -1");
+    assert_eq!(Score::mated_in(6).to_string(), "mate -3");
+    assert_eq!((-Score::mate_in(3)).mate_moves(), Some(-1));
+}
+
+#[test]
+fn mates_are_ordered_by_distance() {
+    assert!(Score::mate_in(1) > Score::mate_in(3));
+    assert!(Score::mated_in(2) < Score::mated_in(4));
+    assert!(Score::mate_in(99) > Score::cp(5000));
+    assert!(Score::INFINITY > Score::mate_in(0));
+    assert!(!Score::cp(5000).is_mate());
+}
+
+#[test]
+fn tt_scores_are_relative_to_the_node() {
+    // Mate in 5 plies from the root, found at ply 3, is a mate in 2 plies from that node
+    let score = Score::mate_in(5);
+    assert_eq!(score.to_tt(3), Score::mate_in(2));
+    assert_eq!(score.to_tt(3).to_search(3), score);
+    // Reached again at ply 1, the same node is mated 3 plies from the root
+    assert_eq!(score.to_tt(3).to_search(1), Score::mate_in(3));
+    assert_eq!(Score::mated_in(4).to_tt(2).to_search(2), Score::mated_in(4));
+    assert_eq!(Score::cp(120).to_tt(7), Score::cp(120));
+}
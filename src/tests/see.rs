@@ -0,0 +1,41 @@
+use crate::search::see::{see, see_ge};
+use chess::{Board, ChessMove};
+use std::str::FromStr;
+
+fn see_of(fen: &str, chess_move: &str) -> i32 {
+    let board = Board::from_str(fen).expect("Invalid FEN");
+    see(&board, ChessMove::from_str(chess_move).unwrap())
+}
+
+#[test]
+fn free_and_defended_pawns() {
+    assert_eq!(see_of("4k3/8/8/3p4/8/8/8/3RK3 w - - 0 1", "d1d5"), 100);
+    // The pawn takes back and the rook is gone
+    assert_eq!(see_of("4k3/2p5/3p4/8/8/8/8/3RK3 w - - 0 1", "d1d6"), -400);
+    assert_eq!(see_of("4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 1", "e5d6"), 100);
+}
+
+#[test]
+fn xrays_join_the_exchange() {
+    // The second rook is behind the first one
+    assert_eq!(see_of("3rk3/8/3p4/8/8/8/3R4/3RK3 w - - 0 1", "d2d6"), 100);
+    // The queen behind the rook keeps the king from taking back
+    assert_eq!(see_of("4k3/3p4/8/8/8/8/3R4/3QK3 w - - 0 1", "d2d7"), 100);
+    assert_eq!(see_of("4k3/3p4/8/8/8/8/3R4/4K3 w - - 0 1", "d2d7"), -400);
+}
+
+#[test]
+fn promotions() {
+    assert_eq!(see_of("4k3/1P6/8/8/8/8/8/4K3 w - - 0 1", "b7b8q"), 800);
+    assert_eq!(see_of("2r1k3/1P6/8/8/8/8/8/4K3 w - - 0 1", "b7b8q"), -100);
+    assert_eq!(see_of("2r1k3/1P6/8/8/8/8/8/4K3 w - - 0 1", "b7c8q"), 1300);
+}
+
+#[test]
+fn thresholds() {
+    let board = Board::from_str("4k3/2p5/3p4/8/8/8/8/3RK3 w - - 0 1").expect("Invalid FEN");
+    let capture = ChessMove::from_str("d1d6").unwrap();
+
+    assert!(see_ge(&board, capture, -400));
+    assert!(!see_ge(&board, capture, 0));
+}
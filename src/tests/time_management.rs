@@ -0,0 +1,80 @@
+use crate::search;
+use crate::search::time_management::{SearchLimits, TimeManager};
+use crate::search::SearchOptions;
+use chess::{Board, Color, MoveGen};
+use std::thread;
+use std::time::{Duration, Instant};
+
+#[test]
+fn budget_uses_the_clock_of_the_side_to_move() {
+    let limits = SearchLimits {
+        wtime: Some(60_000),
+        btime: Some(3_000),
+        ..SearchLimits::default()
+    };
+
+    let white = TimeManager::new(&limits, Color::White);
+    let black = TimeManager::new(&limits, Color::Black);
+
+    assert!(white.soft_limit().unwrap() > black.soft_limit().unwrap());
+    assert!(white.hard_limit().unwrap() >= white.soft_limit().unwrap());
+    // Never plan to use more than half of the remaining time
+    assert!(black.hard_limit().unwrap() <= Duration::from_millis(1_500));
+}
+
+#[test]
+fn no_budget_without_a_clock() {
+    let timer = TimeManager::new(&SearchLimits::depth(5), Color::White);
+    assert!(timer.soft_limit().is_none());
+    assert!(!timer.out_of_time());
+
+    let limits = SearchLimits {
+        wtime: Some(1_000),
+        infinite: true,
+        ..SearchLimits::default()
+    };
+    assert!(TimeManager::new(&limits, Color::White)
+        .hard_limit()
+        .is_none());
+}
+
+#[test]
+fn clock_starts_with_ponderhit() {
+    let limits = SearchLimits {
+        ponder: true,
+        ..SearchLimits::movetime(80)
+    };
+    let timer = TimeManager::new(&limits, Color::White);
+
+    // The budget is long gone, but it's still the opponent's time
+    thread::sleep(Duration::from_millis(100));
+    assert!(timer.is_pondering());
+    assert!(!timer.out_of_time());
+    assert!(timer.can_start_iteration());
+
+    timer.ponderhit();
+    assert!(!timer.is_pondering());
+    assert!(!timer.out_of_time());
+
+    thread::sleep(Duration::from_millis(100));
+    assert!(timer.out_of_time());
+}
+
+#[test]
+fn movetime_search_returns_in_time() {
+    let board = Board::default();
+    let start = Instant::now();
+
+    let best_move = search::iterative_deepening_search(
+        board,
+        &SearchLimits::movetime(300),
+        &SearchOptions::default(),
+        None,
+        None,
+        None,
+    )
+    .best_move;
+
+    assert!(start.elapsed() < Duration::from_secs(3));
+    assert!(MoveGen::new_legal(&board).any(|legal_move| legal_move == best_move));
+}
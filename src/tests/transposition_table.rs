@@ -0,0 +1,137 @@
+use crate::search::score::Score;
+use crate::search::shared_transposition_table::SharedTransTable;
+use crate::search::transposition_table::{Flag, TransTable, TransTableEntry};
+use chess::{Board, ChessMove, MoveGen};
+use std::str::FromStr;
+use std::sync::Arc;
+use std::thread;
+
+fn entry(depth: i32) -> TransTableEntry {
+    TransTableEntry {
+        flag: Flag::Exact,
+        eval: Score::cp(depth),
+        depth,
+        best_move: None,
+    }
+}
+
+// Boards that land in the same bucket of a one bucket table
+fn boards() -> Vec<Board> {
+    MoveGen::new_legal(&Board::default())
+        .map(|chess_move| Board::default().make_move_new(chess_move))
+        .collect()
+}
+
+#[test]
+fn deep_entries_survive_shallow_ones() {
+    let mut tt = TransTable::with_size_mb(0);
+    let boards = boards();
+
+    tt.add_entry(boards[0], entry(8));
+    tt.add_entry(boards[1], entry(2));
+    tt.add_entry(boards[2], entry(3));
+
+    assert_eq!(tt.get(boards[0].get_hash()).unwrap().depth, 8);
+    assert!(tt.get(boards[1].get_hash()).is_none());
+    assert_eq!(tt.get(boards[2].get_hash()).unwrap().depth, 3);
+}
+
+#[test]
+fn entries_of_old_searches_are_replaced() {
+    let mut tt = TransTable::with_size_mb(0);
+    let boards = boards();
+
+    tt.add_entry(boards[0], entry(8));
+    tt.new_search();
+    tt.add_entry(boards[1], entry(2));
+
+    // The old deep entry moved to the always replace slot
+    assert_eq!(tt.get(boards[1].get_hash()).unwrap().depth, 2);
+    assert_eq!(tt.get(boards[0].get_hash()).unwrap().depth, 8);
+    assert_eq!(tt.hashfull(), 500);
+
+    tt.clear();
+    assert!(tt.get(boards[0].get_hash()).is_none());
+    assert_eq!(tt.hashfull(), 0);
+}
+
+#[test]
+fn principal_variation_follows_best_moves() {
+    let mut tt = TransTable::new();
+    let board = Board::default();
+    let line = ["e2e4", "e7e5", "g1f3"];
+
+    let mut position = board;
+    for text in line {
+        let chess_move = ChessMove::from_str(text).unwrap();
+        tt.add_entry(
+            position,
+            TransTableEntry {
+                best_move: Some(chess_move),
+                ..entry(1)
+            },
+        );
+        position = position.make_move_new(chess_move);
+    }
+
+    let pv: Vec<String> = tt
+        .principal_variation(board, 10)
+        .iter()
+        .map(ToString::to_string)
+        .collect();
+    assert_eq!(pv, line);
+}
+
+#[test]
+fn shared_table_round_trips_entries() {
+    let tt = SharedTransTable::new();
+    let board = Board::from_str("4k3/1P6/8/8/8/8/8/4K3 w - - 0 1").expect("Invalid FEN");
+    let promotion = ChessMove::from_str("b7b8n").unwrap();
+
+    tt.add_entry(
+        board,
+        TransTableEntry {
+            flag: Flag::Upperbound,
+            eval: Score::mated_in(7),
+            depth: 12,
+            best_move: Some(promotion),
+        },
+    );
+
+    let entry = tt.get(board.get_hash()).unwrap();
+    assert_eq!(entry.flag, Flag::Upperbound);
+    assert_eq!(entry.eval, Score::mated_in(7));
+    assert_eq!(entry.depth, 12);
+    assert_eq!(entry.best_move, Some(promotion));
+    assert!(tt.get(Board::default().get_hash()).is_none());
+}
+
+#[test]
+fn shared_table_is_usable_from_many_threads() {
+    let tt = Arc::new(SharedTransTable::with_size_mb(1));
+    let boards = boards();
+
+    let handles: Vec<_> = (0..4)
+        .map(|thread_index| {
+            let tt = tt.clone();
+            let boards = boards.clone();
+            thread::spawn(move || {
+                for _ in 0..1000 {
+                    for board in &boards {
+                        tt.add_entry(*board, entry(thread_index));
+                        // Whatever is read back is a whole entry written by one of the threads
+                        if let Some(read) = tt.get(board.get_hash()) {
+                            assert_eq!(read.eval, Score::cp(read.depth));
+                        }
+                    }
+                }
+            })
+        })
+        .collect();
+
+    for handle in handles {
+        handle.join().unwrap();
+    }
+
+    assert!(tt.get(boards[0].get_hash()).is_some());
+}
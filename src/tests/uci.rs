@@ -0,0 +1,118 @@
+use crate::engine::Engine;
+use crate::search::time_management::SearchLimits;
+use crate::search::SearchOptions;
+use crate::uci::{parse_go, parse_position};
+use chess::Board;
+use std::str::FromStr;
+use std::sync::mpsc;
+use std::time::Duration;
+
+#[test]
+fn position_startpos_with_moves() {
+    let (board, history) = parse_position(&["startpos", "moves", "e2e4", "e7e5", "g1f3"]).unwrap();
+    let expected =
+        Board::from_str("rnbqkbnr/pppp1ppp/8/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R b KQkq - 1 2").unwrap();
+    assert_eq!(board, expected);
+    assert_eq!(history.len(), 4);
+    assert_eq!(history.halfmove_clock(), 1);
+}
+
+#[test]
+fn position_fen_with_castling_and_promotion() {
+    let (board, _) = parse_position(&[
+        "fen",
+        "4k3/1P6/8/8/8/8/8/4K2R",
+        "w",
+        "K",
+        "-",
+        "0",
+        "1",
+        "moves",
+        "e1g1",
+        "e8d7",
+        "b7b8q",
+    ])
+    .unwrap();
+    let expected = Board::from_str("1Q6/3k4/8/8/8/8/8/5RK1 b - - 0 2").unwrap();
+    assert_eq!(board, expected);
+}
+
+#[test]
+fn position_fen_keeps_the_halfmove_clock() {
+    let (_, history) = parse_position(&[
+        "fen",
+        "8/8/4k3/8/8/4K3/8/R7",
+        "w",
+        "-",
+        "-",
+        "42",
+        "80",
+        "moves",
+        "a1a2",
+    ])
+    .unwrap();
+    assert_eq!(history.halfmove_clock(), 43);
+}
+
+#[test]
+fn position_rejects_illegal_move() {
+    assert!(parse_position(&["startpos", "moves", "e2e5"]).is_err());
+    assert!(parse_position(&["fen", "not", "a", "fen"]).is_err());
+}
+
+#[test]
+fn go_parameters() {
+    assert_eq!(
+        parse_go(&[
+            "wtime",
+            "60000",
+            "btime",
+            "55000",
+            "winc",
+            "1000",
+            "binc",
+            "500",
+            "movestogo",
+            "20"
+        ]),
+        SearchLimits {
+            wtime: Some(60000),
+            btime: Some(55000),
+            winc: Some(1000),
+            binc: Some(500),
+            movestogo: Some(20),
+            ..SearchLimits::default()
+        }
+    );
+    assert_eq!(
+        parse_go(&["depth", "5", "ponder"]),
+        SearchLimits {
+            ponder: true,
+            ..SearchLimits::depth(5)
+        }
+    );
+    assert_eq!(parse_go(&["movetime", "250"]), SearchLimits::movetime(250));
+    assert!(parse_go(&["infinite"]).infinite);
+}
+
+#[test]
+fn go_infinite_waits_for_stop() {
+    // Mate in one is found right away, bestmove still has to wait for stop
+    let (board, history) =
+        parse_position(&["fen", "6k1/5ppp/8/8/8/8/8/R5K1", "w", "-", "-", "0", "1"]).unwrap();
+    let mut engine = Engine::new();
+    let (sender, receiver) = mpsc::channel();
+
+    engine.go(
+        board,
+        history,
+        &parse_go(&["infinite"]),
+        &SearchOptions::default(),
+        move |result| sender.send(result.best_move).unwrap(),
+    );
+    assert!(receiver.recv_timeout(Duration::from_millis(300)).is_err());
+
+    let result = engine.stop().expect("The search was started");
+    assert_eq!(result.best_move.to_string(), "a1a8");
+    assert_eq!(receiver.try_recv(), Ok(result.best_move));
+}
@@ -0,0 +1,324 @@
+//! UCI front-end so sc0 can be driven by chess GUIs and tournament managers.
+//! Commands are read from stdin one line at a time. Searches run on the threads of the engine so
+//! that `stop`, `isready` and `quit` are still answered while it is thinking.
+
+use std::io::{self, BufRead};
+use std::str::FromStr;
+
+use chess::{Board, ChessMove};
+use log::debug;
+
+use crate::book::{Book, BookSelection};
+use crate::engine::Engine;
+use crate::search;
+use crate::search::draw::GameHistory;
+use crate::search::time_management::SearchLimits;
+use crate::search::transposition_table;
+use crate::search::{RootSearch, SearchOptions};
+
+const ENGINE_NAME: &str = "sc0";
+const ENGINE_AUTHOR: &str = "TauSigma5";
+const DEFAULT_DEPTH: i32 = 7;
+const MAX_HASH_SIZE_MB: usize = 4096;
+const MAX_THREADS: usize = 256;
+
+/// Settings that can be changed by the GUI through `setoption`.
+pub struct EngineOptions {
+    /// Depth used by `go` when the GUI doesn't ask for one
+    pub depth: i32,
+    /// Whether the GUI lets the engine think on the opponent's time, which is when the move it
+    /// expects in reply is worth sending along with the best move
+    pub ponder: bool,
+    /// Moves are taken from here as long as it knows the position
+    pub book: Option<Book>,
+    pub book_selection: BookSelection,
+    pub search: SearchOptions,
+}
+
+impl Default for EngineOptions {
+    fn default() -> Self {
+        EngineOptions {
+            depth: DEFAULT_DEPTH,
+            ponder: false,
+            book: None,
+            book_selection: BookSelection::default(),
+            search: SearchOptions::default(),
+        }
+    }
+}
+
+pub fn uci_loop() {
+    let stdin = io::stdin();
+    let mut board = Board::default();
+    let mut history = GameHistory::new(&board, 0);
+    let mut options = EngineOptions::default();
+    let mut engine = Engine::new();
+
+    for line in stdin.lock().lines() {
+        let line = match line {
+            Ok(line) => line,
+            Err(_) => break,
+        };
+        debug!("UCI command: {}", line);
+
+        let mut tokens = line.split_whitespace();
+        let command = match tokens.next() {
+            Some(command) => command,
+            None => continue,
+        };
+        let args: Vec<&str> = tokens.collect();
+
+        match command {
+            "uci" => {
+                println!("id name {}", ENGINE_NAME);
+                println!("id author {}", ENGINE_AUTHOR);
+                println!(
+                    "option name Depth type spin default {} min 1 max {}",
+                    DEFAULT_DEPTH,
+                    search::MAX_DEPTH
+                );
+                println!(
+                    "option name Hash type spin default {} min 1 max {}",
+                    transposition_table::DEFAULT_SIZE_MB,
+                    MAX_HASH_SIZE_MB
+                );
+                println!(
+                    "option name Threads type spin default {} min 1 max {}",
+                    search::DEFAULT_THREADS,
+                    MAX_THREADS
+                );
+                println!("option name Clear Hash type button");
+                println!("option name Ponder type check default false");
+                println!("option name Book File type string default <empty>");
+                println!(
+                    "option name Book Selection type combo default Weighted var Weighted var Best"
+                );
+                println!("option name Root Search type combo default PVS var PVS var MTDF");
+                println!("option name Recapture Extensions type check default false");
+                println!("option name Passed Pawn Extensions type check default false");
+                println!("uciok");
+            }
+            "isready" => println!("readyok"),
+            "ucinewgame" => {
+                board = Board::default();
+                history = GameHistory::new(&board, 0);
+                engine.new_game();
+            }
+            "position" => {
+                engine.stop();
+                match parse_position(&args) {
+                    Ok((new_board, new_history)) => {
+                        board = new_board;
+                        history = new_history;
+                    }
+                    Err(message) => println!("info string {}", message),
+                }
+            }
+            "go" => {
+                let mut limits = parse_go(&args);
+                // A bare `go` searches to the configured depth
+                if limits == SearchLimits::default() {
+                    limits.depth = Some(options.depth);
+                }
+                start_search(&mut engine, board, history.clone(), &limits, &options);
+            }
+            // The GUI sends `stop` instead when the opponent played something else
+            "ponderhit" => engine.ponderhit(),
+            "stop" => {
+                engine.stop();
+            }
+            "setoption" => {
+                engine.stop();
+                if let Err(message) = set_option(&args, &mut options, &mut engine) {
+                    println!("info string {}", message);
+                }
+            }
+            "quit" => {
+                engine.stop();
+                break;
+            }
+            // The protocol says unknown commands should be ignored
+            _ => debug!("Ignoring unknown UCI command {}", command),
+        }
+    }
+
+    engine.stop();
+}
+
+fn start_search(
+    engine: &mut Engine,
+    board: Board,
+    history: GameHistory,
+    limits: &SearchLimits,
+    options: &EngineOptions,
+) {
+    if board.status() != chess::BoardStatus::Ongoing {
+        // GUIs expect an answer even when there is nothing to play
+        println!("bestmove 0000");
+        return;
+    }
+
+    if let Some(book_move) = options.book.as_ref().and_then(|book| book.pick(&board)) {
+        println!("bestmove {}", book_move);
+        return;
+    }
+
+    let tt = engine.table();
+    let ponder = options.ponder;
+    engine.go(board, history, limits, &options.search, move |result| {
+        let pv: Vec<String> = result.pv.iter().map(ToString::to_string).collect();
+        println!(
+            "info depth {} score {} hashfull {} pv {}",
+            result.depth,
+            result.eval,
+            tt.hashfull(),
+            pv.join(" ")
+        );
+        match result.pv.get(1).filter(|_| ponder) {
+            Some(ponder_move) => println!("bestmove {} ponder {}", result.best_move, ponder_move),
+            None => println!("bestmove {}", result.best_move),
+        }
+    });
+}
+
+/// Parses the arguments of `position startpos|fen <fen> [moves <move>...]`. Besides the
+/// position itself, returns the positions the moves went through for draw detection.
+pub fn parse_position(args: &[&str]) -> Result<(Board, GameHistory), String> {
+    let moves_index = args.iter().position(|arg| *arg == "moves");
+    let (setup, moves) = match moves_index {
+        Some(i) => (&args[..i], &args[i + 1..]),
+        None => (args, &args[args.len()..]),
+    };
+
+    let (mut board, halfmove_clock) = match setup.first() {
+        Some(&"startpos") => (Board::default(), 0),
+        Some(&"fen") => {
+            let fen = setup[1..].join(" ");
+            let board = Board::from_str(&fen).map_err(|_| format!("Invalid FEN {}", fen))?;
+            // The crate doesn't keep the halfmove clock, so it's read from the FEN here
+            let halfmove_clock = setup.get(5).and_then(|clock| clock.parse().ok());
+            (board, halfmove_clock.unwrap_or(0))
+        }
+        _ => return Err("Expected startpos or fen after position".to_string()),
+    };
+    let mut history = GameHistory::new(&board, halfmove_clock);
+
+    for move_text in moves {
+        let chess_move =
+            ChessMove::from_str(move_text).map_err(|_| format!("Invalid move {}", move_text))?;
+
+        if !board.legal(chess_move) {
+            return Err(format!("Illegal move {}", move_text));
+        }
+
+        history.push(&board, chess_move);
+        board = board.make_move_new(chess_move);
+    }
+
+    Ok((board, history))
+}
+
+/// Parses the arguments of `go`. Parameters sc0 doesn't support yet are skipped.
+pub fn parse_go(args: &[&str]) -> SearchLimits {
+    let mut limits = SearchLimits::default();
+    let mut tokens = args.iter();
+
+    while let Some(token) = tokens.next() {
+        match *token {
+            "wtime" => limits.wtime = parse_next(&mut tokens),
+            "btime" => limits.btime = parse_next(&mut tokens),
+            "winc" => limits.winc = parse_next(&mut tokens),
+            "binc" => limits.binc = parse_next(&mut tokens),
+            "movestogo" => limits.movestogo = parse_next(&mut tokens),
+            "movetime" => limits.movetime = parse_next(&mut tokens),
+            "depth" => limits.depth = parse_next(&mut tokens),
+            "infinite" => limits.infinite = true,
+            "ponder" => limits.ponder = true,
+            _ => {}
+        }
+    }
+
+    limits
+}
+
+fn parse_next<'a, T: FromStr>(tokens: &mut impl Iterator<Item = &'a &'a str>) -> Option<T> {
+    tokens.next().and_then(|value| value.parse().ok())
+}
+
+fn set_option(
+    args: &[&str],
+    options: &mut EngineOptions,
+    engine: &mut Engine,
+) -> Result<(), String> {
+    // Option names may contain spaces, so everything between `name` and `value` is the name
+    let value_index = args.iter().position(|arg| *arg == "value");
+    let name = match args.first() {
+        Some(&"name") => args[1..value_index.unwrap_or(args.len())].join(" "),
+        _ => return Err("Expected name after setoption".to_string()),
+    };
+    let value = value_index.map(|i| args[i + 1..].join(" "));
+
+    match name.to_lowercase().as_str() {
+        "depth" => {
+            let depth = value
+                .and_then(|value| value.parse::<i32>().ok())
+                .filter(|depth| (1..=search::MAX_DEPTH).contains(depth))
+                .ok_or_else(|| "Invalid value for Depth".to_string())?;
+            options.depth = depth;
+        }
+        "hash" => {
+            let size = value
+                .and_then(|value| value.parse::<usize>().ok())
+                .filter(|size| (1..=MAX_HASH_SIZE_MB).contains(size))
+                .ok_or_else(|| "Invalid value for Hash".to_string())?;
+            engine.resize_hash(size);
+        }
+        "threads" => {
+            let threads = value
+                .and_then(|value| value.parse::<usize>().ok())
+                .filter(|threads| (1..=MAX_THREADS).contains(threads))
+                .ok_or_else(|| "Invalid value for Threads".to_string())?;
+            options.search.threads = threads;
+        }
+        "clear hash" => engine.clear_hash(),
+        "ponder" => options.ponder = parse_check(value, "Ponder")?,
+        "book file" => {
+            options.book = match value.filter(|path| !path.is_empty() && path != "<empty>") {
+                Some(path) => Some(Book::open(path, options.book_selection)?),
+                None => None,
+            };
+        }
+        "book selection" => {
+            options.book_selection = match value.map(|value| value.to_lowercase()).as_deref() {
+                Some("weighted") => BookSelection::Weighted,
+                Some("best") => BookSelection::Best,
+                _ => return Err("Invalid value for Book Selection".to_string()),
+            };
+            if let Some(book) = &mut options.book {
+                book.set_selection(options.book_selection);
+            }
+        }
+        "root search" => {
+            options.search.root_search = match value.map(|value| value.to_lowercase()).as_deref() {
+                Some("pvs") => RootSearch::Pvs,
+                Some("mtdf") => RootSearch::Mtdf,
+                _ => return Err("Invalid value for Root Search".to_string()),
+            };
+        }
+        "recapture extensions" => {
+            options.search.recapture_extensions = parse_check(value, "Recapture Extensions")?
+        }
+        "passed pawn extensions" => {
+            options.search.passed_pawn_extensions = parse_check(value, "Passed Pawn Extensions")?
+        }
+        _ => return Err(format!("Unknown option {}", name)),
+    }
+
+    Ok(())
+}
+
+fn parse_check(value: Option<String>, name: &str) -> Result<bool, String> {
+    value
+        .and_then(|value| value.parse().ok())
+        .ok_or_else(|| format!("Invalid value for {}", name))
+}